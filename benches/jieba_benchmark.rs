@@ -2,7 +2,7 @@
 extern crate criterion;
 
 use criterion::{black_box, Benchmark, Criterion, ParameterizedBenchmark, Throughput};
-use jieba_rs::{Jieba, KeywordExtract, TextRank, TokenizeMode, TFIDF};
+use jieba_rs::{CutOptions, Jieba, KeywordExtract, TextRank, TokenizeMode, TFIDF};
 use lazy_static::lazy_static;
 
 #[cfg(unix)]
@@ -13,10 +13,21 @@ lazy_static! {
     static ref JIEBA: Jieba = Jieba::new();
     static ref TFIDF_EXTRACTOR: TFIDF<'static> = TFIDF::new_with_jieba(&JIEBA);
     static ref TEXTRANK_EXTRACTOR: TextRank<'static> = TextRank::new_with_jieba(&JIEBA);
+    // Every position starts several dict words, "一一", "一一对应", "一一列举"...
+    static ref HIGH_FANOUT: String = "一一对应一一列举".repeat(200);
+    // Mostly words of 3 chars or more, each of which search mode looks up grams in
+    static ref LONG_DOCUMENT: String = LONG_TOKENS.repeat(100);
 }
 static SENTENCE: &str = "我是拖拉机学院手扶拖拉机专业的。不用多久，我就会升职加薪，当上CEO，走上人生巅峰。";
+static LONG_TOKENS: &str =
+    "中华人民共和国中国科学院计算所手扶拖拉机专业北京烤鸭中华人民共和国宪法全国人民代表大会常务委员会";
 
 fn criterion_benchmark(c: &mut Criterion) {
+    c.bench(
+        "jieba construction",
+        Benchmark::new("empty", |b| b.iter(Jieba::empty)).with_function("new", |b| b.iter(Jieba::new)),
+    );
+
     c.bench(
         "jieba cut",
         ParameterizedBenchmark::new(
@@ -32,6 +43,42 @@ fn criterion_benchmark(c: &mut Criterion) {
         .throughput(|i| Throughput::Bytes(i.len() as u64)),
     );
 
+    c.bench(
+        "jieba cut single word",
+        ParameterizedBenchmark::new(
+            "no hmm",
+            |b, i| b.iter(|| JIEBA.cut(black_box(i), false)),
+            vec!["北京大学", "中华人民共和国"],
+        )
+        .with_function("with hmm", |b, i| b.iter(|| JIEBA.cut(black_box(i), true)))
+        .throughput(|i| Throughput::Bytes(i.len() as u64)),
+    );
+
+    c.bench(
+        "jieba cut dictionary heavy",
+        ParameterizedBenchmark::new(
+            "no hmm",
+            |b, i| b.iter(|| JIEBA.cut(black_box(i), false)),
+            vec![LONG_TOKENS],
+        )
+        .with_function("with hmm", |b, i| b.iter(|| JIEBA.cut(black_box(i), true)))
+        .throughput(|i| Throughput::Bytes(i.len() as u64)),
+    );
+
+    c.bench(
+        "jieba cut high fanout",
+        ParameterizedBenchmark::new(
+            "unbounded",
+            |b, i| b.iter(|| JIEBA.cut(black_box(i), false)),
+            vec![HIGH_FANOUT.as_str()],
+        )
+        .with_function("max_match_chars 2", |b, i| {
+            let options = CutOptions::default().max_match_chars(Some(2));
+            b.iter(|| JIEBA.cut_with_options(black_box(i), &options))
+        })
+        .throughput(|i| Throughput::Bytes(i.len() as u64)),
+    );
+
     c.bench(
         "jieba tokenize",
         ParameterizedBenchmark::new(
@@ -45,6 +92,27 @@ fn criterion_benchmark(c: &mut Criterion) {
         .throughput(|i| Throughput::Bytes(i.len() as u64)),
     );
 
+    c.bench(
+        "jieba search mode long tokens",
+        ParameterizedBenchmark::new(
+            "cut_for_search",
+            |b, i| b.iter(|| JIEBA.cut_for_search(black_box(i), false)),
+            vec![LONG_TOKENS],
+        )
+        .with_function("tokenize", |b, i| {
+            b.iter(|| JIEBA.tokenize(black_box(i), TokenizeMode::Search, false))
+        })
+        .throughput(|i| Throughput::Bytes(i.len() as u64)),
+    );
+
+    c.bench(
+        "jieba search mode long document",
+        Benchmark::new("cut_for_search", |b| {
+            b.iter(|| JIEBA.cut_for_search(black_box(&LONG_DOCUMENT), false))
+        })
+        .throughput(Throughput::Bytes(LONG_DOCUMENT.len() as u64)),
+    );
+
     c.bench(
         "jieba",
         Benchmark::new("tag", |b| b.iter(|| JIEBA.tag(black_box(SENTENCE), true)))
@@ -1,12 +1,21 @@
 use c_fixed_string::CFixedStr;
 use jieba_rs::{Jieba, KeywordExtract, TextRank, TFIDF};
 use std::boxed::Box;
-use std::os::raw::c_char;
-use std::{mem, ptr};
+use std::os::raw::{c_char, c_int};
+use std::{mem, panic, ptr, slice, str};
 
 pub struct CJieba;
 pub struct CJiebaTFIDF;
 
+/// The call succeeded
+pub const JIEBA_OK: c_int = 0;
+/// A required pointer argument was null
+pub const JIEBA_ERR_NULL_POINTER: c_int = 1;
+/// The input text is not valid UTF-8
+pub const JIEBA_ERR_INVALID_UTF8: c_int = 2;
+/// Segmentation panicked, the panic was caught at the FFI boundary
+pub const JIEBA_ERR_PANIC: c_int = 3;
+
 #[repr(C)]
 pub struct CJiebaWords {
     pub words: *mut FfiStr,
@@ -152,6 +161,53 @@ pub unsafe extern "C" fn jieba_cut(j: *mut CJieba, sentence: *const c_char, len:
     }))
 }
 
+/// Cut the sentence, rejecting invalid UTF-8 instead of replacing it
+///
+/// On success returns `JIEBA_OK` and stores the words into `*out`, which must then be freed
+/// with `jieba_words_free`. On failure returns one of the `JIEBA_ERR_*` codes and leaves
+/// `*out` untouched.
+///
+/// # Safety
+///
+/// `j` must be a pointer returned by `jieba_new` or `jieba_empty`, `sentence` must point to
+/// at least `len` readable bytes and `out` must be valid for writes.
+#[no_mangle]
+pub unsafe extern "C" fn jieba_cut_checked(
+    j: *mut CJieba,
+    sentence: *const c_char,
+    len: usize,
+    hmm: bool,
+    out: *mut *mut CJiebaWords,
+) -> c_int {
+    if j.is_null() || out.is_null() || (sentence.is_null() && len > 0) {
+        return JIEBA_ERR_NULL_POINTER;
+    }
+    let bytes = if len == 0 {
+        &[]
+    } else {
+        slice::from_raw_parts(sentence as *const u8, len)
+    };
+    let s = match str::from_utf8(bytes) {
+        Ok(s) => s,
+        Err(_) => return JIEBA_ERR_INVALID_UTF8,
+    };
+    let jieba = &*(j as *const Jieba);
+    // Cutting only reads the instance, so a panic can't leave it in a broken state
+    let words = match panic::catch_unwind(panic::AssertUnwindSafe(|| jieba.cut(s, hmm))) {
+        Ok(words) => words,
+        Err(_) => return JIEBA_ERR_PANIC,
+    };
+    let mut c_words: Vec<FfiStr> = words.into_iter().map(|x| FfiStr::from_string(x.to_string())).collect();
+    let words_len = c_words.len();
+    let ptr = c_words.as_mut_ptr();
+    mem::forget(c_words);
+    *out = Box::into_raw(Box::new(CJiebaWords {
+        words: ptr,
+        len: words_len,
+    }));
+    JIEBA_OK
+}
+
 #[no_mangle]
 pub unsafe extern "C" fn jieba_cut_all(j: *mut CJieba, sentence: *const c_char, len: usize) -> *mut CJiebaWords {
     let jieba = j as *mut Jieba;
@@ -404,6 +460,48 @@ mod test {
         }
     }
 
+    #[test]
+    fn test_jieba_cut_checked() {
+        unsafe {
+            let jieba = jieba_new();
+            let sentence = "我们中出了一个叛徒";
+            let mut words: *mut CJiebaWords = ptr::null_mut();
+            let ret = jieba_cut_checked(
+                jieba,
+                sentence.as_ptr() as *const c_char,
+                sentence.len(),
+                true,
+                &mut words,
+            );
+            assert_eq!(ret, JIEBA_OK);
+            let c_words = slice::from_raw_parts((*words).words, (*words).len);
+            let cut: Vec<&str> = c_words
+                .iter()
+                .map(|w| str::from_utf8(slice::from_raw_parts(w.data as *const u8, w.len)).unwrap())
+                .collect();
+            assert_eq!(cut, vec!["我们", "中出", "了", "一个", "叛徒"]);
+            jieba_words_free(words);
+
+            let invalid = b"\xe6\x88\x91\xff";
+            let mut words: *mut CJiebaWords = ptr::null_mut();
+            let ret = jieba_cut_checked(
+                jieba,
+                invalid.as_ptr() as *const c_char,
+                invalid.len(),
+                true,
+                &mut words,
+            );
+            assert_eq!(ret, JIEBA_ERR_INVALID_UTF8);
+            assert!(words.is_null());
+
+            let ret = jieba_cut_checked(jieba, ptr::null(), 3, true, &mut words);
+            assert_eq!(ret, JIEBA_ERR_NULL_POINTER);
+            assert!(words.is_null());
+
+            jieba_free(jieba);
+        }
+    }
+
     #[test]
     fn test_jieba_add_word() {
         unsafe {
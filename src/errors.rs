@@ -7,6 +7,13 @@ pub enum Error {
     Io(io::Error),
     /// Invalid entry in dictionary
     InvalidDictEntry(String),
+    /// Invalid entry in a dictionary line
+    InvalidDictLine {
+        /// 1-based line number of the entry
+        line: usize,
+        /// What is wrong with the entry
+        message: String,
+    },
 }
 
 impl From<io::Error> for Error {
@@ -20,6 +27,9 @@ impl fmt::Display for Error {
         match *self {
             Error::Io(ref err) => err.fmt(f),
             Error::InvalidDictEntry(ref err) => write!(f, "invalid dictionary entry: {}", err),
+            Error::InvalidDictLine { line, ref message } => {
+                write!(f, "invalid dictionary entry: line {}: {}", line, message)
+            }
         }
     }
 }
@@ -28,7 +38,7 @@ impl error::Error for Error {
     fn source(&self) -> Option<&(dyn error::Error + 'static)> {
         match *self {
             Error::Io(ref err) => Some(err),
-            Error::InvalidDictEntry(_) => None,
+            Error::InvalidDictEntry(_) | Error::InvalidDictLine { .. } => None,
         }
     }
 }
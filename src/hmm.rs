@@ -9,16 +9,21 @@ use crate::SplitMatches;
 
 lazy_static! {
     static ref RE_HAN: Regex = Regex::new(r"([\u{4E00}-\u{9FD5}]+)").unwrap();
-    static ref RE_SKIP: Regex = Regex::new(r"([a-zA-Z0-9]+(?:.\d+)?%?)").unwrap();
+    static ref RE_SKIP: Regex = Regex::new(r"([+#&._%]*[a-zA-Z0-9][a-zA-Z0-9+#&._%]*)").unwrap();
 }
 
 pub type StatusSet = [f64; 4];
 
+/// The state the HMM labels a char with, relative to the word it's part of
 #[derive(Debug, PartialEq, Eq, Hash, PartialOrd, Ord, Clone, Copy)]
 pub enum Status {
+    /// Begins a word of two chars or more
     B = 0,
+    /// Ends a word of two chars or more
     E = 1,
+    /// In the middle of a word of three chars or more
     M = 2,
+    /// A single char word
     S = 3,
 }
 
@@ -155,6 +160,19 @@ pub fn cut_internal<'a>(
     path.clear();
 }
 
+/// Label every char of `sentence` with its most likely state
+pub(crate) fn tag(sentence: &str) -> Vec<(char, Status)> {
+    match sentence.chars().count() {
+        0 => Vec::new(),
+        1 => sentence.chars().map(|ch| (ch, Status::S)).collect(),
+        _ => {
+            let mut path = Vec::new();
+            viterbi(sentence, &mut Vec::new(), &mut Vec::new(), &mut path);
+            sentence.chars().zip(path).collect()
+        }
+    }
+}
+
 #[allow(non_snake_case)]
 pub(crate) fn cut_with_allocated_memory<'a>(
     sentence: &'a str,
@@ -188,6 +206,7 @@ pub(crate) fn cut_with_allocated_memory<'a>(
     }
 }
 
+#[cfg(test)]
 #[allow(non_snake_case)]
 pub fn cut<'a>(sentence: &'a str, words: &mut Vec<&'a str>) {
     let R = 4;
@@ -201,7 +220,7 @@ pub fn cut<'a>(sentence: &'a str, words: &mut Vec<&'a str>) {
 
 #[cfg(test)]
 mod tests {
-    use super::{cut, viterbi, Status};
+    use super::{cut, tag, viterbi, Status};
 
     #[test]
     #[allow(non_snake_case)]
@@ -219,6 +238,15 @@ mod tests {
         assert_eq!(path, vec![B, E, B, E, B, M, E, B, E, B, M, E, B, E, S]);
     }
 
+    #[test]
+    fn test_tag() {
+        use super::Status::*;
+
+        assert_eq!(tag(""), vec![]);
+        assert_eq!(tag("中"), vec![('中', S)]);
+        assert_eq!(tag("小明硕士"), vec![('小', B), ('明', E), ('硕', B), ('士', E)]);
+    }
+
     #[test]
     fn test_hmm_cut() {
         let sentence = "小明硕士毕业于中国科学院计算所";
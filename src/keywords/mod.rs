@@ -1,3 +1,4 @@
+use hashbrown::HashMap;
 use lazy_static::lazy_static;
 use std::collections::BTreeSet;
 
@@ -23,6 +24,107 @@ lazy_static! {
     };
 }
 
+/// A keyword with its score and the `[start, end)` char offset ranges it occurs at
+pub type KeywordPositions = (String, f64, Vec<(usize, usize)>);
+
 pub trait KeywordExtract {
     fn extract_tags<'a>(&'a self, _: &'a str, _: usize, _: Vec<String>) -> Vec<String>;
+
+    /// Extract the `top_k` keywords of every document, in the order of `docs`
+    ///
+    /// The result is the same as calling `extract_tags` on every document, implementations may
+    /// share work between the documents.
+    fn extract_tags_batch(&self, docs: &[&str], top_k: usize, allowed_pos: Vec<String>) -> Vec<Vec<String>> {
+        docs.iter()
+            .map(|doc| self.extract_tags(doc, top_k, allowed_pos.clone()))
+            .collect()
+    }
+}
+
+/// Attach to every ranked keyword the char offset ranges of the `words` equal to it
+pub(crate) fn keyword_positions(words: &[&str], keywords: Vec<(String, f64)>) -> Vec<KeywordPositions> {
+    let index: HashMap<String, usize> = keywords
+        .iter()
+        .enumerate()
+        .map(|(i, (word, _))| (word.clone(), i))
+        .collect();
+    let mut res: Vec<KeywordPositions> = keywords
+        .into_iter()
+        .map(|(word, score)| (word, score, Vec::new()))
+        .collect();
+
+    let mut start = 0;
+    for word in words {
+        let end = start + word.chars().count();
+        if let Some(&i) = index.get(*word) {
+            res[i].2.push((start, end));
+        }
+        start = end;
+    }
+
+    res
+}
+
+/// Join keywords which are adjacent in the text into key phrases
+///
+/// Every run of two or more consecutive `words` which are all among `keywords` forms a key
+/// phrase. Each keyword is then replaced by the first phrase it's part of, keeping the rank
+/// order of `keywords` and removing duplicates, so fewer keywords than given may be returned.
+pub(crate) fn merge_phrases(words: &[&str], keywords: Vec<String>) -> Vec<String> {
+    let mut phrases: HashMap<&str, String> = HashMap::new();
+    {
+        let keyword_set: BTreeSet<&str> = keywords.iter().map(String::as_str).collect();
+        let mut i = 0;
+        while i < words.len() {
+            let mut j = i;
+            while j < words.len() && keyword_set.contains(words[j]) {
+                j += 1;
+            }
+
+            if j - i > 1 {
+                let phrase = words[i..j].concat();
+                for word in &words[i..j] {
+                    phrases.entry(word).or_insert_with(|| phrase.clone());
+                }
+            }
+            i = j.max(i + 1);
+        }
+    }
+
+    let mut seen = BTreeSet::new();
+    keywords
+        .into_iter()
+        .map(|keyword| phrases.get(keyword.as_str()).cloned().unwrap_or(keyword))
+        .filter(|keyword| seen.insert(keyword.clone()))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{keyword_positions, merge_phrases};
+
+    #[test]
+    fn test_merge_phrases() {
+        let words = ["吉林", "欧亚", "置业", "，", "欧亚", "商场", "实现", "收入"];
+        let keywords = vec![
+            String::from("欧亚"),
+            String::from("吉林"),
+            String::from("收入"),
+            String::from("置业"),
+        ];
+        assert_eq!(merge_phrases(&words, keywords), vec!["吉林欧亚置业", "收入"]);
+    }
+
+    #[test]
+    fn test_keyword_positions() {
+        let words = ["纽约", "的", "天气", "，", "纽约"];
+        let keywords = vec![(String::from("纽约"), 2.0), (String::from("天气"), 1.0)];
+        assert_eq!(
+            keyword_positions(&words, keywords),
+            vec![
+                (String::from("纽约"), 2.0, vec![(0, 2), (6, 8)]),
+                (String::from("天气"), 1.0, vec![(3, 5)]),
+            ]
+        );
+    }
 }
@@ -1,5 +1,5 @@
-use super::{KeywordExtract, STOP_WORDS};
-use crate::Jieba;
+use super::{keyword_positions, merge_phrases, KeywordExtract, KeywordPositions, STOP_WORDS};
+use crate::{Jieba, Tag};
 use hashbrown::HashMap;
 use std::cmp::Ordering;
 use std::collections::{BTreeSet, BinaryHeap};
@@ -28,9 +28,9 @@ struct StateDiagram {
 }
 
 impl StateDiagram {
-    fn new(size: usize) -> Self {
+    fn new(size: usize, damping_factor: Weight) -> Self {
         StateDiagram {
-            damping_factor: 0.85,
+            damping_factor,
             g: vec![Vec::new(); size],
         }
     }
@@ -40,7 +40,7 @@ impl StateDiagram {
         self.g[dst].push(Edge::new(dst, src, weight));
     }
 
-    fn rank(&mut self) -> Vec<Weight> {
+    fn rank(&mut self, iterations: usize) -> Vec<Weight> {
         let n = self.g.len();
         let default_weight = 1.0 / (n as f64);
 
@@ -51,7 +51,7 @@ impl StateDiagram {
             outflow_weights[i] = v.iter().map(|e| e.weight).sum();
         }
 
-        for _ in 0..20 {
+        for _ in 0..iterations {
             for (i, v) in self.g.iter().enumerate() {
                 let s: f64 = v
                     .iter()
@@ -73,43 +73,100 @@ impl StateDiagram {
 pub struct TextRank<'a> {
     jieba: &'a Jieba,
     span: usize,
+    iterations: usize,
+    damping_factor: Weight,
+    merge_phrases: bool,
+    min_word_len: usize,
 }
 
 impl<'a> TextRank<'a> {
     pub fn new_with_jieba(jieba: &'a Jieba) -> Self {
-        TextRank { jieba, span: 5 }
+        TextRank {
+            jieba,
+            span: 5,
+            iterations: 20,
+            damping_factor: 0.85,
+            merge_phrases: false,
+            min_word_len: 2,
+        }
     }
-}
 
-impl<'a> KeywordExtract for TextRank<'a> {
-    fn extract_tags(&self, sentence: &str, top_k: usize, allowed_pos: Vec<String>) -> Vec<String> {
-        let tags = self.jieba.tag(sentence, true);
-        let mut allowed_pos_set = BTreeSet::new();
+    /// Join the extracted keywords which are adjacent in the text into key phrases, disabled by default
+    ///
+    /// A run of words is merged only if the words follow each other in the text and every one
+    /// of them is among the `top_k` keywords. Each keyword is replaced by the first phrase it's
+    /// part of, so fewer than `top_k` keywords may be returned.
+    pub fn set_merge_phrases(&mut self, merge_phrases: bool) {
+        self.merge_phrases = merge_phrases;
+    }
 
-        for s in allowed_pos {
-            allowed_pos_set.insert(s);
-        }
+    /// Set the minimum number of chars of a keyword, defaults to 2
+    ///
+    /// Shorter words are dropped before ranking, single chars are rarely useful keywords.
+    pub fn set_min_word_len(&mut self, min_word_len: usize) {
+        self.min_word_len = min_word_len;
+    }
+
+    /// Set the number of ranking iterations, defaults to 20
+    ///
+    /// Ranks are updated in place, so they usually converge within a few dozen iterations;
+    /// more iterations only matter for large graphs.
+    pub fn set_iterations(&mut self, iterations: usize) {
+        self.iterations = iterations;
+    }
+
+    /// Set the damping factor, defaults to 0.85
+    ///
+    /// A lower damping factor makes the ranks converge faster but weights the graph
+    /// structure less, pulling all ranks closer to each other.
+    ///
+    /// ## Panics
+    ///
+    /// Panics if `damping_factor` is not within `(0, 1)`.
+    pub fn set_damping(&mut self, damping_factor: f64) {
+        assert!(
+            damping_factor > 0.0 && damping_factor < 1.0,
+            "damping factor must be within (0, 1), got {}",
+            damping_factor
+        );
+        self.damping_factor = damping_factor;
+    }
+
+    /// Rank already cut and tagged words, return the `top_k` keywords with their rank
+    ///
+    /// Unlike `extract_tags` no segmentation happens, `tagged` is a sequence of `(word, tag)`
+    /// pairs in text order, so externally tokenized text can be ranked as well.
+    pub fn rank_tokens(&self, tagged: &[(String, String)], top_k: usize, allowed_pos: &[String]) -> Vec<(String, f64)> {
+        let words: Vec<(&str, &str)> = tagged.iter().map(|(word, tag)| (word.as_str(), tag.as_str())).collect();
+        let allowed_pos_set: BTreeSet<&str> = allowed_pos.iter().map(String::as_str).collect();
+        self.rank(&words, top_k, &allowed_pos_set)
+    }
 
+    fn rank(&self, tags: &[(&str, &str)], top_k: usize, allowed_pos_set: &BTreeSet<&str>) -> Vec<(String, f64)> {
         let mut word2id: HashMap<String, usize> = HashMap::new();
         let mut unique_words: Vec<String> = Vec::new();
-        for t in &tags {
-            if !allowed_pos_set.is_empty() && !allowed_pos_set.contains(t.tag) {
+        for &(word, tag) in tags {
+            if !allowed_pos_set.is_empty() && !allowed_pos_set.contains(tag) {
+                continue;
+            }
+
+            if !filter(word, self.min_word_len) {
                 continue;
             }
 
-            if word2id.get(t.word).is_none() {
-                unique_words.push(String::from(t.word));
-                word2id.insert(String::from(t.word), unique_words.len() - 1);
+            if word2id.get(word).is_none() {
+                unique_words.push(String::from(word));
+                word2id.insert(String::from(word), unique_words.len() - 1);
             }
         }
 
         let mut cooccurence: HashMap<(usize, usize), usize> = HashMap::new();
-        for (i, t) in tags.iter().enumerate() {
-            if !allowed_pos_set.is_empty() && !allowed_pos_set.contains(t.tag) {
+        for (i, &(word, tag)) in tags.iter().enumerate() {
+            if !allowed_pos_set.is_empty() && !allowed_pos_set.contains(tag) {
                 continue;
             }
 
-            if !filter(t.word) {
+            if !filter(word, self.min_word_len) {
                 continue;
             }
 
@@ -118,27 +175,27 @@ impl<'a> KeywordExtract for TextRank<'a> {
                     break;
                 }
 
-                if !allowed_pos_set.is_empty() && !allowed_pos_set.contains(tags[j].tag) {
+                if !allowed_pos_set.is_empty() && !allowed_pos_set.contains(tags[j].1) {
                     continue;
                 }
 
-                if !filter(tags[j].word) {
+                if !filter(tags[j].0, self.min_word_len) {
                     continue;
                 }
 
-                let u = word2id.get(t.word).unwrap().to_owned();
-                let v = word2id.get(tags[j].word).unwrap().to_owned();
+                let u = word2id.get(word).unwrap().to_owned();
+                let v = word2id.get(tags[j].0).unwrap().to_owned();
                 let entry = cooccurence.entry((u, v)).or_insert(0);
                 *entry += 1;
             }
         }
 
-        let mut diagram = StateDiagram::new(unique_words.len());
+        let mut diagram = StateDiagram::new(unique_words.len(), self.damping_factor);
         for (k, &v) in cooccurence.iter() {
             diagram.add_undirected_edge(k.0, k.1, v as f64);
         }
 
-        let ranking_vector = diagram.rank();
+        let ranking_vector = diagram.rank(self.iterations);
 
         let mut heap = BinaryHeap::new();
         for (k, v) in ranking_vector.iter().enumerate() {
@@ -152,16 +209,85 @@ impl<'a> KeywordExtract for TextRank<'a> {
             }
         }
 
-        let mut res: Vec<String> = Vec::new();
+        let mut res: Vec<(String, f64)> = Vec::new();
         for _ in 0..top_k {
             if let Some(w) = heap.pop() {
-                res.push(unique_words[w.word_id].clone());
+                res.push((unique_words[w.word_id].clone(), ranking_vector[w.word_id]));
             }
         }
 
         res.reverse();
         res
     }
+
+    /// Extract the `top_k` keywords of already cut and tagged text
+    ///
+    /// `tokens` are `(word, tag)` pairs in text order, ranked like the words `extract_tags` cuts
+    /// the text into, so the segmentation is left to the caller.
+    pub fn extract_from_tokens(
+        &self,
+        tokens: &[(String, String)],
+        top_k: usize,
+        allowed_pos: &[String],
+    ) -> Vec<String> {
+        let tags: Vec<Tag> = tokens.iter().map(|(word, tag)| Tag { word, tag }).collect();
+        let allowed_pos_set: BTreeSet<&str> = allowed_pos.iter().map(String::as_str).collect();
+        self.keywords(&tags, top_k, &allowed_pos_set)
+    }
+
+    /// Extract the `top_k` keywords along with their score and every char offset range they occur at
+    ///
+    /// Occurrences are the `[start, end)` char ranges of the cut words equal to the keyword, in text
+    /// order. Keywords are never merged into key phrases here.
+    pub fn extract_tags_with_positions(
+        &self,
+        sentence: &str,
+        top_k: usize,
+        allowed_pos: Vec<String>,
+    ) -> Vec<KeywordPositions> {
+        let tags = self.jieba.tag(sentence, true);
+        let words: Vec<(&str, &str)> = tags.iter().map(|t| (t.word, t.tag)).collect();
+        let allowed_pos_set: BTreeSet<&str> = allowed_pos.iter().map(String::as_str).collect();
+
+        let keywords = self.rank(&words, top_k, &allowed_pos_set);
+        let words: Vec<&str> = tags.iter().map(|t| t.word).collect();
+        keyword_positions(&words, keywords)
+    }
+
+    fn keywords(&self, tags: &[Tag], top_k: usize, allowed_pos_set: &BTreeSet<&str>) -> Vec<String> {
+        let words: Vec<(&str, &str)> = tags.iter().map(|t| (t.word, t.tag)).collect();
+        let keywords = self
+            .rank(&words, top_k, allowed_pos_set)
+            .into_iter()
+            .map(|(word, _)| word)
+            .collect();
+
+        if self.merge_phrases {
+            let words: Vec<&str> = tags.iter().map(|t| t.word).collect();
+            merge_phrases(&words, keywords)
+        } else {
+            keywords
+        }
+    }
+}
+
+impl<'a> KeywordExtract for TextRank<'a> {
+    fn extract_tags(&self, sentence: &str, top_k: usize, allowed_pos: Vec<String>) -> Vec<String> {
+        let tags = self.jieba.tag(sentence, true);
+        let allowed_pos_set: BTreeSet<&str> = allowed_pos.iter().map(String::as_str).collect();
+        self.keywords(&tags, top_k, &allowed_pos_set)
+    }
+
+    /// Same as `extract_tags` on every document, the documents are tagged with
+    /// [`Jieba::tag_batch`](struct.Jieba.html#method.tag_batch), in parallel with the `rayon` feature
+    fn extract_tags_batch(&self, docs: &[&str], top_k: usize, allowed_pos: Vec<String>) -> Vec<Vec<String>> {
+        let allowed_pos_set: BTreeSet<&str> = allowed_pos.iter().map(String::as_str).collect();
+        self.jieba
+            .tag_batch(docs, true)
+            .iter()
+            .map(|tags| self.keywords(tags, top_k, &allowed_pos_set))
+            .collect()
+    }
 }
 
 #[derive(Debug, Clone, Eq, PartialEq)]
@@ -186,8 +312,8 @@ impl PartialOrd for HeapNode {
 }
 
 #[inline]
-fn filter(s: &str) -> bool {
-    if s.chars().count() < 2 {
+fn filter(s: &str, min_word_len: usize) -> bool {
+    if s.chars().count() < min_word_len {
         return false;
     }
 
@@ -210,10 +336,111 @@ mod tests {
 
     #[test]
     fn test_init_state_diagram() {
-        let diagram = StateDiagram::new(10);
+        let diagram = StateDiagram::new(10, 0.85);
         assert_eq!(diagram.g.len(), 10);
     }
 
+    #[test]
+    fn test_rank_tokens() {
+        let jieba = Jieba::new();
+        let keyword_extractor = TextRank::new_with_jieba(&jieba);
+        let tagged: Vec<(String, String)> = [
+            ("吉林", "ns"),
+            ("欧亚", "ns"),
+            ("置业", "vn"),
+            ("的", "uj"),
+            ("欧亚", "ns"),
+            ("商场", "n"),
+            ("，", "x"),
+            ("吉林", "ns"),
+            ("欧亚", "ns"),
+            ("百货", "n"),
+        ]
+        .iter()
+        .map(|&(word, tag)| (String::from(word), String::from(tag)))
+        .collect();
+
+        let ranked = keyword_extractor.rank_tokens(&tagged, 3, &[]);
+        assert_eq!(ranked.len(), 3);
+        assert_eq!(ranked[0].0, "欧亚");
+        assert!(ranked.windows(2).all(|w| w[0].1 >= w[1].1));
+
+        let ranked = keyword_extractor.rank_tokens(&tagged, 10, &[String::from("n")]);
+        let mut words: Vec<&str> = ranked.iter().map(|(word, _)| word.as_str()).collect();
+        words.sort();
+        assert_eq!(words, vec!["商场", "百货"]);
+    }
+
+    #[test]
+    fn test_set_damping_and_iterations() {
+        let jieba = Jieba::new();
+        let mut keyword_extractor = TextRank::new_with_jieba(&jieba);
+        let tagged: Vec<(String, String)> = jieba
+            .tag(
+                "今天纽约的天气真好啊，京华大酒店的张尧经理吃了一只北京烤鸭。后天纽约的天气不好",
+                true,
+            )
+            .into_iter()
+            .map(|t| (String::from(t.word), String::from(t.tag)))
+            .collect();
+
+        let default_ranks = keyword_extractor.rank_tokens(&tagged, 3, &[]);
+        keyword_extractor.set_damping(0.5);
+        let ranks = keyword_extractor.rank_tokens(&tagged, 3, &[]);
+        assert_ne!(ranks[0].1, default_ranks[0].1);
+
+        keyword_extractor.set_damping(0.85);
+        keyword_extractor.set_iterations(0);
+        let ranks = keyword_extractor.rank_tokens(&tagged, 3, &[]);
+        assert!(ranks.iter().all(|(_, rank)| *rank == ranks[0].1));
+
+        keyword_extractor.set_iterations(20);
+        let ranks = keyword_extractor.rank_tokens(&tagged, 3, &[]);
+        for ((word, rank), (default_word, default_rank)) in ranks.iter().zip(default_ranks.iter()) {
+            assert_eq!(word, default_word);
+            assert!((rank - default_rank).abs() < 1e-9);
+        }
+    }
+
+    #[test]
+    fn test_set_min_word_len() {
+        let jieba = Jieba::new();
+        let mut keyword_extractor = TextRank::new_with_jieba(&jieba);
+        let sentence = "今天纽约的天气真好啊，京华大酒店的张尧经理吃了一只北京烤鸭。后天纽约的天气不好";
+        let top_k = keyword_extractor.extract_tags(sentence, 50, vec![]);
+        assert!(top_k.iter().all(|word| word.chars().count() >= 2));
+
+        keyword_extractor.set_min_word_len(1);
+        let top_k = keyword_extractor.extract_tags(sentence, 50, vec![]);
+        assert!(top_k.iter().any(|word| word.chars().count() == 1));
+
+        keyword_extractor.set_min_word_len(3);
+        let top_k = keyword_extractor.extract_tags(sentence, 50, vec![]);
+        assert!(top_k.iter().all(|word| word.chars().count() >= 3));
+        assert!(top_k.contains(&String::from("北京烤鸭")));
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_set_invalid_damping() {
+        let jieba = Jieba::empty();
+        let mut keyword_extractor = TextRank::new_with_jieba(&jieba);
+        keyword_extractor.set_damping(1.0);
+    }
+
+    #[test]
+    fn test_extract_phrases() {
+        let jieba = Jieba::new();
+        let mut keyword_extractor = TextRank::new_with_jieba(&jieba);
+        keyword_extractor.set_merge_phrases(true);
+        let top_k = keyword_extractor.extract_tags(
+            "此外，公司拟对全资子公司吉林欧亚置业有限公司增资4.3亿元，增资后，吉林欧亚置业注册资本由7000万元增加到5亿元。吉林欧亚置业主要经营范围为房地产开发及百货零售等业务。目前在建吉林欧亚城市商业综合体项目。2013年，实现营业收入0万元，实现净利润-139.13万元。",
+            6,
+            vec![String::from("ns"), String::from("n"), String::from("vn"), String::from("v")],
+        );
+        assert_eq!(top_k, vec!["吉林欧亚置业", "实现", "收入", "增资"]);
+    }
+
     #[test]
     fn test_extract_tags() {
         let jieba = Jieba::new();
@@ -232,4 +459,64 @@ mod tests {
         );
         assert_eq!(top_k, vec!["纽约", "天气", "不好"]);
     }
+
+    #[test]
+    fn test_extract_from_tokens() {
+        let jieba = Jieba::new();
+        let keyword_extractor = TextRank::new_with_jieba(&jieba);
+        let tokens: Vec<(String, String)> = [
+            ("吉林欧亚", "nz"),
+            ("置业", "vn"),
+            ("的", "uj"),
+            ("吉林欧亚", "nz"),
+            ("商场", "n"),
+            ("，", "x"),
+            ("吉林欧亚", "nz"),
+            ("百货", "n"),
+        ]
+        .iter()
+        .map(|&(word, tag)| (String::from(word), String::from(tag)))
+        .collect();
+
+        let top_k = keyword_extractor.extract_from_tokens(&tokens, 2, &[]);
+        assert_eq!(top_k.len(), 2);
+        assert_eq!(top_k[0], "吉林欧亚");
+        let ranked: Vec<String> = keyword_extractor
+            .rank_tokens(&tokens, 2, &[])
+            .into_iter()
+            .map(|(word, _)| word)
+            .collect();
+        assert_eq!(top_k, ranked);
+    }
+
+    #[test]
+    fn test_extract_tags_batch() {
+        let jieba = Jieba::new();
+        let keyword_extractor = TextRank::new_with_jieba(&jieba);
+        let docs = [
+            "今天纽约的天气真好啊，京华大酒店的张尧经理吃了一只北京烤鸭。后天纽约的天气不好，昨天纽约的天气也不好，北京烤鸭真好吃",
+            "此外，公司拟对全资子公司吉林欧亚置业有限公司增资4.3亿元，增资后，吉林欧亚置业注册资本由7000万元增加到5亿元。",
+            "吉林欧亚置业主要经营范围为房地产开发及百货零售等业务。目前在建吉林欧亚城市商业综合体项目。",
+        ];
+        let batch = keyword_extractor.extract_tags_batch(&docs, 3, vec![]);
+        let single: Vec<Vec<String>> = docs
+            .iter()
+            .map(|doc| keyword_extractor.extract_tags(doc, 3, vec![]))
+            .collect();
+        assert_eq!(batch, single);
+        assert!(batch.iter().all(|keywords| keywords.len() == 3));
+    }
+
+    #[test]
+    fn test_extract_tags_with_positions() {
+        let jieba = Jieba::new();
+        let keyword_extractor = TextRank::new_with_jieba(&jieba);
+        let sentence = "今天纽约的天气真好啊，京华大酒店的张尧经理吃了一只北京烤鸭。后天纽约的天气不好，昨天纽约的天气也不好，北京烤鸭真好吃";
+        let top_k = keyword_extractor.extract_tags_with_positions(sentence, 3, vec![]);
+        let words: Vec<&str> = top_k.iter().map(|(word, _, _)| word.as_str()).collect();
+        assert_eq!(words, keyword_extractor.extract_tags(sentence, 3, vec![]));
+
+        let (_, _, positions) = top_k.iter().find(|(word, _, _)| word == "纽约").unwrap();
+        assert_eq!(positions, &vec![(2, 4), (32, 34), (42, 44)]);
+    }
 }
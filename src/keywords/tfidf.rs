@@ -1,8 +1,8 @@
-use super::{KeywordExtract, STOP_WORDS};
-use crate::Jieba;
+use super::{keyword_positions, merge_phrases, KeywordExtract, KeywordPositions, STOP_WORDS};
+use crate::{Jieba, Tag};
 use hashbrown::HashMap;
 use std::cmp::Ordering;
-use std::collections::{BTreeSet, BinaryHeap};
+use std::collections::{BTreeSet, BinaryHeap, HashSet};
 use std::io::{self, BufRead, BufReader};
 
 static DEFAULT_IDF: &str = include_str!("../data/idf.txt");
@@ -33,6 +33,8 @@ pub struct TFIDF<'a> {
     jieba: &'a Jieba,
     idf_dict: HashMap<String, u64>,
     median_idf: u64,
+    merge_phrases: bool,
+    min_word_len: usize,
 }
 
 impl<'a> TFIDF<'a> {
@@ -41,6 +43,8 @@ impl<'a> TFIDF<'a> {
             jieba,
             idf_dict: HashMap::new(),
             median_idf: 0,
+            merge_phrases: false,
+            min_word_len: 2,
         };
 
         let mut default_dict = BufReader::new(DEFAULT_IDF.as_bytes());
@@ -48,6 +52,65 @@ impl<'a> TFIDF<'a> {
         instance
     }
 
+    /// Create an instance with IDF values computed from `docs` instead of the default IDF dict
+    ///
+    /// Every document is cut without HMM and the number of documents each word appears in is
+    /// counted. The IDF of a word is smoothed as `ln((1 + N) / (1 + df)) + 1`, with `N` the number
+    /// of documents and `df` the number of documents containing the word, so it stays positive
+    /// even for a word appearing in every document. Words which aren't in any of the documents
+    /// get the median IDF, like words missing from a loaded IDF dict.
+    pub fn from_documents(jieba: &'a Jieba, docs: &[&str]) -> Self {
+        let mut doc_freq: HashMap<&str, usize> = HashMap::new();
+        for doc in docs {
+            let words: HashSet<&str> = jieba.cut(doc, false).into_iter().collect();
+            for word in words {
+                *doc_freq.entry(word).or_insert(0) += 1;
+            }
+        }
+
+        let n = docs.len() as f64;
+        let idf_dict: HashMap<String, u64> = doc_freq
+            .into_iter()
+            .map(|(word, df)| {
+                let idf = ((1.0 + n) / (1.0 + df as f64)).ln() + 1.0;
+                //using fix-point integer but not f64
+                (String::from(word), (idf * 1e10) as u64)
+            })
+            .collect();
+
+        let mut idfs: Vec<u64> = idf_dict.values().copied().collect();
+        idfs.sort_unstable();
+        let median_idf = if idfs.is_empty() {
+            0
+        } else {
+            idfs[idfs.len() - 1 - idfs.len() / 2]
+        };
+
+        TFIDF {
+            jieba,
+            idf_dict,
+            median_idf,
+            merge_phrases: false,
+            min_word_len: 2,
+        }
+    }
+
+    /// Join the extracted keywords which are adjacent in the text into key phrases, disabled by default
+    ///
+    /// A run of words is merged only if the words follow each other in the text and every one
+    /// of them is among the `top_k` keywords. Each keyword is replaced by the first phrase it's
+    /// part of, so fewer than `top_k` keywords may be returned.
+    pub fn set_merge_phrases(&mut self, merge_phrases: bool) {
+        self.merge_phrases = merge_phrases;
+    }
+
+    /// Set the minimum number of chars of a keyword, defaults to 2
+    ///
+    /// Shorter words are dropped before ranking, single chars are rarely useful keywords.
+    pub fn set_min_word_len(&mut self, min_word_len: usize) {
+        self.min_word_len = min_word_len;
+    }
+
     pub fn load_dict<R: BufRead>(&mut self, dict: &mut R) -> io::Result<()> {
         let mut buf = String::new();
         let mut idf_heap = BinaryHeap::new();
@@ -78,11 +141,8 @@ impl<'a> TFIDF<'a> {
 
         Ok(())
     }
-}
 
-impl<'a> KeywordExtract for TFIDF<'a> {
-    fn extract_tags(&self, sentence: &str, top_k: usize, allowed_pos: Vec<String>) -> Vec<String> {
-        let tags = self.jieba.tag(sentence, false);
+    fn rank(&self, tags: &[Tag], top_k: usize, allowed_pos: Vec<String>) -> Vec<(String, f64)> {
         let mut allowed_pos_set = BTreeSet::new();
 
         for s in allowed_pos {
@@ -90,12 +150,12 @@ impl<'a> KeywordExtract for TFIDF<'a> {
         }
 
         let mut term_freq: HashMap<String, u64> = HashMap::new();
-        for t in &tags {
+        for t in tags {
             if !allowed_pos_set.is_empty() && !allowed_pos_set.contains(t.tag) {
                 continue;
             }
 
-            if !filter(t.word) {
+            if !filter(t.word, self.min_word_len) {
                 continue;
             }
 
@@ -125,21 +185,82 @@ impl<'a> KeywordExtract for TFIDF<'a> {
             }
         }
 
-        let mut res: Vec<String> = Vec::new();
+        let mut res: Vec<(String, f64)> = Vec::new();
         for _ in 0..top_k {
             if let Some(w) = heap.pop() {
-                res.push(String::from(w.word));
+                res.push((String::from(w.word), w.tfidf as f64 / 1e10));
             }
         }
 
         res.reverse();
         res
     }
+
+    /// Extract the `top_k` keywords of already cut and tagged text
+    ///
+    /// `tokens` are `(word, tag)` pairs in text order, ranked like the words `extract_tags` cuts
+    /// the text into, so the segmentation is left to the caller.
+    pub fn extract_from_tokens(
+        &self,
+        tokens: &[(String, String)],
+        top_k: usize,
+        allowed_pos: &[String],
+    ) -> Vec<String> {
+        let tags: Vec<Tag> = tokens.iter().map(|(word, tag)| Tag { word, tag }).collect();
+        self.keywords(&tags, top_k, allowed_pos.to_vec())
+    }
+
+    /// Extract the `top_k` keywords along with their score and every char offset range they occur at
+    ///
+    /// Occurrences are the `[start, end)` char ranges of the cut words equal to the keyword, in text
+    /// order. Keywords are never merged into key phrases here.
+    pub fn extract_tags_with_positions(
+        &self,
+        sentence: &str,
+        top_k: usize,
+        allowed_pos: Vec<String>,
+    ) -> Vec<KeywordPositions> {
+        let tags = self.jieba.tag(sentence, false);
+        let words: Vec<&str> = tags.iter().map(|t| t.word).collect();
+        keyword_positions(&words, self.rank(&tags, top_k, allowed_pos))
+    }
+
+    fn keywords(&self, tags: &[Tag], top_k: usize, allowed_pos: Vec<String>) -> Vec<String> {
+        let res = self
+            .rank(tags, top_k, allowed_pos)
+            .into_iter()
+            .map(|(word, _)| word)
+            .collect();
+
+        if self.merge_phrases {
+            let words: Vec<&str> = tags.iter().map(|t| t.word).collect();
+            merge_phrases(&words, res)
+        } else {
+            res
+        }
+    }
+}
+
+impl<'a> KeywordExtract for TFIDF<'a> {
+    fn extract_tags(&self, sentence: &str, top_k: usize, allowed_pos: Vec<String>) -> Vec<String> {
+        let tags = self.jieba.tag(sentence, false);
+        self.keywords(&tags, top_k, allowed_pos)
+    }
+
+    /// Same as `extract_tags` on every document, the documents are tagged with
+    /// [`Jieba::tag_batch`](struct.Jieba.html#method.tag_batch), in parallel with the `rayon` feature
+    fn extract_tags_batch(&self, docs: &[&str], top_k: usize, allowed_pos: Vec<String>) -> Vec<Vec<String>> {
+        self.jieba
+            .tag_batch(docs, false)
+            .iter()
+            .map(|tags| self.keywords(tags, top_k, allowed_pos.clone()))
+            .collect()
+    }
 }
 
 #[inline]
-fn filter(s: &str) -> bool {
-    if s.chars().count() < 2 {
+fn filter(s: &str, min_word_len: usize) -> bool {
+    if s.chars().count() < min_word_len {
         return false;
     }
 
@@ -160,6 +281,37 @@ mod tests {
         let _ = TFIDF::new_with_jieba(&jieba);
     }
 
+    #[test]
+    fn test_from_documents() {
+        let jieba = super::Jieba::new();
+        let docs = ["我们喜欢北京烤鸭", "我们去纽约", "我们在上海"];
+        let keyword_extractor = TFIDF::from_documents(&jieba, &docs);
+        let idf = |word: &str| keyword_extractor.idf_dict[word];
+        assert!(idf("我们") < idf("纽约"));
+        assert_eq!(idf("纽约"), idf("北京烤鸭"));
+        assert_eq!(keyword_extractor.median_idf, idf("纽约"));
+
+        let top_k = keyword_extractor.extract_tags("我们我们我们喜欢北京烤鸭", 2, vec![]);
+        assert_eq!(top_k, vec!["我们", "北京烤鸭"]);
+
+        let keyword_extractor = TFIDF::from_documents(&jieba, &[]);
+        assert!(keyword_extractor.idf_dict.is_empty());
+        assert_eq!(keyword_extractor.median_idf, 0);
+    }
+
+    #[test]
+    fn test_extract_phrases() {
+        let jieba = super::Jieba::new();
+        let mut keyword_extractor = TFIDF::new_with_jieba(&jieba);
+        keyword_extractor.set_merge_phrases(true);
+        let top_k = keyword_extractor.extract_tags(
+            "此外，公司拟对全资子公司吉林欧亚置业有限公司增资4.3亿元，增资后，吉林欧亚置业注册资本由7000万元增加到5亿元。吉林欧亚置业主要经营范围为房地产开发及百货零售等业务。目前在建吉林欧亚城市商业综合体项目。2013年，实现营业收入0万元，实现净利润-139.13万元。",
+            5,
+            vec![],
+        );
+        assert_eq!(top_k, vec!["吉林欧亚置业", "万元", "增资"]);
+    }
+
     #[test]
     fn test_extract_tags() {
         let jieba = super::Jieba::new();
@@ -185,4 +337,86 @@ mod tests {
         );
         assert_eq!(top_k, vec!["欧亚", "吉林", "置业", "增资", "实现"]);
     }
+
+    #[test]
+    fn test_extract_from_tokens() {
+        let jieba = super::Jieba::new();
+        let keyword_extractor = TFIDF::new_with_jieba(&jieba);
+        let tokens: Vec<(String, String)> = [
+            ("吉林欧亚", "nz"),
+            ("置业", "vn"),
+            ("增资", "v"),
+            ("，", "x"),
+            ("吉林欧亚", "nz"),
+            ("注册资本", "n"),
+            ("增加", "v"),
+        ]
+        .iter()
+        .map(|&(word, tag)| (String::from(word), String::from(tag)))
+        .collect();
+
+        let top_k = keyword_extractor.extract_from_tokens(&tokens, 2, &[]);
+        assert_eq!(top_k[0], "吉林欧亚");
+        assert_eq!(top_k.len(), 2);
+        let top_k = keyword_extractor.extract_from_tokens(&tokens, 2, &[String::from("v")]);
+        assert_eq!(top_k, vec!["增资", "增加"]);
+    }
+
+    #[test]
+    fn test_extract_tags_batch() {
+        let jieba = super::Jieba::new();
+        let mut keyword_extractor = TFIDF::new_with_jieba(&jieba);
+        keyword_extractor.set_merge_phrases(true);
+        let docs = [
+            "今天纽约的天气真好啊，京华大酒店的张尧经理吃了一只北京烤鸭。后天纽约的天气不好，昨天纽约的天气也不好，北京烤鸭真好吃",
+            "此外，公司拟对全资子公司吉林欧亚置业有限公司增资4.3亿元，增资后，吉林欧亚置业注册资本由7000万元增加到5亿元。",
+            "",
+        ];
+        let allowed_pos = vec![String::from("ns"), String::from("n")];
+        let batch = keyword_extractor.extract_tags_batch(&docs, 3, allowed_pos.clone());
+        let single: Vec<Vec<String>> = docs
+            .iter()
+            .map(|doc| keyword_extractor.extract_tags(doc, 3, allowed_pos.clone()))
+            .collect();
+        assert_eq!(batch, single);
+        assert!(!batch[0].is_empty() && !batch[1].is_empty() && batch[2].is_empty());
+    }
+
+    #[test]
+    fn test_set_min_word_len() {
+        let jieba = super::Jieba::new();
+        let mut keyword_extractor = TFIDF::new_with_jieba(&jieba);
+        let sentence = "今天纽约的天气真好啊，京华大酒店的张尧经理吃了一只北京烤鸭。后天纽约的天气不好";
+        let top_k = keyword_extractor.extract_tags(sentence, 50, vec![]);
+        assert!(top_k.iter().all(|word| word.chars().count() >= 2));
+
+        keyword_extractor.set_min_word_len(1);
+        let top_k = keyword_extractor.extract_tags(sentence, 50, vec![]);
+        assert!(top_k.iter().any(|word| word.chars().count() == 1));
+
+        keyword_extractor.set_min_word_len(3);
+        let top_k = keyword_extractor.extract_tags(sentence, 50, vec![]);
+        assert!(top_k.iter().all(|word| word.chars().count() >= 3));
+        assert!(top_k.contains(&String::from("北京烤鸭")));
+    }
+
+    #[test]
+    fn test_extract_tags_with_positions() {
+        let jieba = super::Jieba::new();
+        let keyword_extractor = TFIDF::new_with_jieba(&jieba);
+        let sentence = "今天纽约的天气真好啊，京华大酒店的张尧经理吃了一只北京烤鸭。后天纽约的天气不好，昨天纽约的天气也不好，北京烤鸭真好吃";
+        let top_k = keyword_extractor.extract_tags_with_positions(sentence, 3, vec![]);
+        let words: Vec<&str> = top_k.iter().map(|(word, _, _)| word.as_str()).collect();
+        assert_eq!(words, keyword_extractor.extract_tags(sentence, 3, vec![]));
+        assert!(top_k.windows(2).all(|w| w[0].1 >= w[1].1));
+
+        let chars: Vec<char> = sentence.chars().collect();
+        let (_, _, positions) = top_k.iter().find(|(word, _, _)| word == "纽约").unwrap();
+        assert_eq!(positions, &vec![(2, 4), (32, 34), (42, 44)]);
+        for (word, _, positions) in &top_k {
+            for &(start, end) in positions {
+                assert_eq!(&chars[start..end].iter().collect::<String>(), word);
+            }
+        }
+    }
 }
@@ -0,0 +1,135 @@
+use std::fmt;
+use std::sync::OnceLock;
+
+use crate::{Jieba, Tag};
+
+/// A [`Jieba`](struct.Jieba.html) whose dict is only loaded on first use
+///
+/// Creating it is nearly free, the dict is parsed and the automata are built by the first call
+/// which needs them, which is then that much slower. Useful for tools which don't always cut
+/// text. Loading happens at most once, even when several threads use the instance at the same
+/// time.
+///
+/// ```rust
+/// use jieba_rs::LazyJieba;
+///
+/// let jieba = LazyJieba::new();
+/// assert!(!jieba.is_loaded());
+/// assert_eq!(jieba.cut("我们中出了一个叛徒", false), vec!["我们", "中", "出", "了", "一个", "叛徒"]);
+/// assert!(jieba.is_loaded());
+/// ```
+pub struct LazyJieba {
+    inner: OnceLock<Jieba>,
+    loader: Box<dyn Fn() -> Jieba + Send + Sync>,
+}
+
+impl LazyJieba {
+    /// Create an instance loading the embedded dict on first use
+    ///
+    /// Requires the `default-dict` feature.
+    #[cfg(feature = "default-dict")]
+    pub fn new() -> Self {
+        LazyJieba::with_loader(Jieba::new)
+    }
+
+    /// Create an instance which calls `loader` on first use to build the `Jieba` it delegates to
+    pub fn with_loader<F: Fn() -> Jieba + Send + Sync + 'static>(loader: F) -> Self {
+        LazyJieba {
+            inner: OnceLock::new(),
+            loader: Box::new(loader),
+        }
+    }
+
+    /// Whether the dict has been loaded yet
+    pub fn is_loaded(&self) -> bool {
+        self.inner.get().is_some()
+    }
+
+    /// Return the loaded instance, loading it first if needed
+    pub fn get(&self) -> &Jieba {
+        self.inner.get_or_init(|| (self.loader)())
+    }
+
+    /// Cut the input text, loading the dict first if needed
+    ///
+    /// ## Params
+    ///
+    /// `sentence`: input text
+    ///
+    /// `hmm`: enable HMM or not
+    pub fn cut<'a>(&self, sentence: &'a str, hmm: bool) -> Vec<&'a str> {
+        self.get().cut(sentence, hmm)
+    }
+
+    /// Cut the input text in search mode, loading the dict first if needed
+    ///
+    /// ## Params
+    ///
+    /// `sentence`: input text
+    ///
+    /// `hmm`: enable HMM or not
+    pub fn cut_for_search<'a>(&self, sentence: &'a str, hmm: bool) -> Vec<&'a str> {
+        self.get().cut_for_search(sentence, hmm)
+    }
+
+    /// Tag the input text, loading the dict first if needed
+    ///
+    /// ## Params
+    ///
+    /// `sentence`: input text
+    ///
+    /// `hmm`: enable HMM or not
+    pub fn tag<'a>(&'a self, sentence: &'a str, hmm: bool) -> Vec<Tag<'a>> {
+        self.get().tag(sentence, hmm)
+    }
+}
+
+#[cfg(feature = "default-dict")]
+impl Default for LazyJieba {
+    fn default() -> Self {
+        LazyJieba::new()
+    }
+}
+
+impl fmt::Debug for LazyJieba {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("LazyJieba").field("inner", &self.inner).finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::LazyJieba;
+    use crate::Jieba;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    #[test]
+    fn test_load_on_first_use() {
+        let loads = Arc::new(AtomicUsize::new(0));
+        let jieba = {
+            let loads = Arc::clone(&loads);
+            LazyJieba::with_loader(move || {
+                loads.fetch_add(1, Ordering::SeqCst);
+                Jieba::with_dict_str("南京 100\n市长 100\n").unwrap()
+            })
+        };
+        assert!(!jieba.is_loaded());
+        assert_eq!(loads.load(Ordering::SeqCst), 0);
+
+        assert_eq!(jieba.cut("南京市长", false), vec!["南京", "市长"]);
+        assert!(jieba.is_loaded());
+        assert_eq!(jieba.tag("南京", false)[0].word, "南京");
+        assert_eq!(loads.load(Ordering::SeqCst), 1);
+    }
+
+    #[test]
+    fn test_default_dict() {
+        let jieba = LazyJieba::new();
+        assert!(!jieba.is_loaded());
+        assert_eq!(
+            jieba.cut("我来到北京清华大学", false),
+            Jieba::new().cut("我来到北京清华大学", false)
+        );
+    }
+}
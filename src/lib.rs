@@ -60,6 +60,14 @@
 //! * `default-dict` feature enables embedded dictionary, this features is enabled by default
 //! * `tfidf` feature enables TF-IDF keywords extractor
 //! * `textrank` feature enables TextRank keywords extractor
+//! * `rayon` feature enables parallel batch tagging with [`Jieba::tag_batch`](struct.Jieba.html#method.tag_batch)
+//! * `grapheme` feature splits text which isn't cut by the dict into grapheme clusters instead of
+//!   chars and counts token offsets in grapheme clusters, so sequences like emoji joined by ZWJ
+//!   stay whole
+//! * `mmap` feature enables [`Jieba::from_mmap`](struct.Jieba.html#method.from_mmap), which loads
+//!   a dict file through a memory map instead of reading it into a buffer
+//! * `shared` feature enables [`SharedJieba`](struct.SharedJieba.html), which can be shared between
+//!   threads and have its dict replaced without blocking the threads cutting text
 //!
 //! ```toml
 //! [dependencies]
@@ -69,36 +77,381 @@
 
 use lazy_static::lazy_static;
 
+use std::borrow::Cow;
 use std::cmp::Ordering;
-use std::io::{self, BufRead};
+use std::collections::{HashMap, VecDeque};
+use std::fmt;
+use std::io::{self, BufRead, Write};
+use std::iter::FromIterator;
 
 use cedarwood::Cedar;
+use hashbrown::HashSet;
 use regex::{Match, Matches, Regex};
+#[cfg(feature = "grapheme")]
+use unicode_segmentation::UnicodeSegmentation;
 
 pub use crate::errors::Error;
+pub use crate::hmm::Status as HmmStatus;
 #[cfg(feature = "textrank")]
 pub use crate::keywords::textrank::TextRank;
 #[cfg(feature = "tfidf")]
 pub use crate::keywords::tfidf::TFIDF;
 #[cfg(any(feature = "tfidf", feature = "textrank"))]
-pub use crate::keywords::KeywordExtract;
+pub use crate::keywords::{KeywordExtract, KeywordPositions};
+pub use crate::lazy::LazyJieba;
+#[cfg(feature = "shared")]
+pub use crate::shared::SharedJieba;
 
 mod errors;
 mod hmm;
 #[cfg(any(feature = "tfidf", feature = "textrank"))]
 mod keywords;
+mod lazy;
+#[cfg(feature = "shared")]
+mod shared;
 mod sparse_dag;
 
 #[cfg(feature = "default-dict")]
 static DEFAULT_DICT: &str = include_str!("data/dict.txt");
 
+#[cfg(feature = "default-dict")]
+lazy_static! {
+    // Instance with the embed dict, parsed on first use by `reset_dict` and cloned from then on
+    static ref DEFAULT_JIEBA: Jieba = Jieba::new();
+}
+
 use sparse_dag::StaticSparseDAG;
 
+// Default regexes, compiled once and cloned into every instance. Cloning a `Regex` shares
+// the compiled program, so instances using the defaults don't compile them again.
 lazy_static! {
     static ref RE_HAN_DEFAULT: Regex = Regex::new(r"([\u{3400}-\u{4DBF}\u{4E00}-\u{9FFF}\u{F900}-\u{FAFF}\u{20000}-\u{2A6DF}\u{2A700}-\u{2B73F}\u{2B740}-\u{2B81F}\u{2B820}-\u{2CEAF}\u{2CEB0}-\u{2EBEF}\u{2F800}-\u{2FA1F}a-zA-Z0-9+#&\._%]+)").unwrap();
     static ref RE_SKIP_DEAFULT: Regex = Regex::new(r"(\r\n|\s)").unwrap();
     static ref RE_HAN_CUT_ALL: Regex = Regex::new(r"([\u{3400}-\u{4DBF}\u{4E00}-\u{9FFF}\u{F900}-\u{FAFF}\u{20000}-\u{2A6DF}\u{2A700}-\u{2B73F}\u{2B740}-\u{2B81F}\u{2B820}-\u{2CEAF}\u{2CEB0}-\u{2EBEF}\u{2F800}-\u{2FA1F}]+)").unwrap();
     static ref RE_SKIP_CUT_ALL: Regex = Regex::new(r"[^a-zA-Z0-9+#\n]").unwrap();
+    static ref RE_NUMBER: Regex = Regex::new(r"(?:[0-9]+(?:\.[0-9]+)?[十百千万亿]*|[零〇一二两三四五六七八九十][零〇一二两三四五六七八九十百千万亿]+)[元年月日号岁个次倍%]?").unwrap();
+}
+
+/// Split text into sentences, without segmenting them
+///
+/// A sentence ends with a run of the terminators `。！？；…` or `.!?`, which stays attached to it
+/// together with the closing quotes and brackets `”’」』）》】)"'` and the whitespace following the
+/// run. An ASCII `.` followed by a letter or digit, like in "v2.0", doesn't end a sentence.
+///
+/// Terminators inside the quotes and brackets `“‘「『（《【(` don't end a sentence, a quote
+/// ending with a terminator ends the sentence at its closing mark instead. Quotes aren't expected
+/// to span lines, an unclosed quote is dropped at the next `\n`.
+///
+/// ```rust
+/// use jieba_rs::split_sentences;
+///
+/// assert_eq!(split_sentences("你好。再见！"), vec!["你好。", "再见！"]);
+/// ```
+pub fn split_sentences(text: &str) -> Vec<&str> {
+    let is_terminator = |ch: char| "。！？；….!?".contains(ch);
+    let is_opening = |ch: char| "“‘「『（《【(".contains(ch);
+    let is_closing = |ch: char| "”’」』）》】)".contains(ch);
+
+    let mut sentences = Vec::new();
+    let mut sentence_start = 0;
+    let mut depth = 0usize;
+    let mut chars = text.char_indices().peekable();
+    while let Some((_, ch)) = chars.next() {
+        if ch == '\n' {
+            depth = 0;
+        } else if is_opening(ch) {
+            depth += 1;
+        } else if is_closing(ch) {
+            depth = depth.saturating_sub(1);
+        }
+        if !is_terminator(ch) {
+            continue;
+        }
+        if ch == '.' && matches!(chars.peek(), Some(&(_, next)) if next.is_ascii_alphanumeric()) {
+            continue;
+        }
+        while let Some(&(_, next)) = chars.peek() {
+            if is_closing(next) {
+                depth = depth.saturating_sub(1);
+            } else if !is_terminator(next) && next != '"' && next != '\'' {
+                break;
+            }
+            chars.next();
+        }
+        if depth > 0 {
+            continue;
+        }
+        while matches!(chars.peek(), Some(&(_, next)) if next.is_whitespace()) {
+            chars.next();
+        }
+        let sentence_end = chars.peek().map_or(text.len(), |&(byte_index, _)| byte_index);
+        sentences.push(&text[sentence_start..sentence_end]);
+        sentence_start = sentence_end;
+    }
+    if sentence_start < text.len() {
+        sentences.push(&text[sentence_start..]);
+    }
+    sentences
+}
+
+/// Slice of `sentence` from the start of `first` to the end of `last`, both being slices of
+/// `sentence`
+fn join_words<'a>(sentence: &'a str, first: &str, last: &str) -> &'a str {
+    let offset = |word: &str| word.as_ptr() as usize - sentence.as_ptr() as usize;
+    &sentence[offset(first)..offset(last) + last.len()]
+}
+
+/// Merge the runs of adjacent words of the same class into one word, `words` being consecutive
+/// slices of `sentence`
+///
+/// Words whose class is `None` are never merged. Every merged word is returned with the class
+/// of its run.
+fn merge_runs<'a, C, F>(sentence: &'a str, words: Vec<&'a str>, mut class: F) -> Vec<(&'a str, Option<C>)>
+where
+    C: PartialEq,
+    F: FnMut(&str) -> Option<C>,
+{
+    let mut merged: Vec<(&'a str, Option<C>)> = Vec::with_capacity(words.len());
+    for word in words {
+        let word_class = class(word);
+        match merged.last_mut() {
+            Some((prev, prev_class)) if word_class.is_some() && word_class == *prev_class => {
+                *prev = join_words(sentence, prev, word);
+            }
+            _ => merged.push((word, word_class)),
+        }
+    }
+    merged
+}
+
+/// Class of a punctuation char for [`collapse_punctuation`], `None` if `ch` isn't punctuation
+fn punctuation_class(ch: char) -> Option<char> {
+    if ch.is_alphanumeric() || ch.is_whitespace() {
+        None
+    } else if ".。．…".contains(ch) {
+        Some('。')
+    } else if "-‐–—―－".contains(ch) {
+        Some('—')
+    } else {
+        Some(ch)
+    }
+}
+
+/// Merge the runs of single punctuation chars of the same class into one word, `words` being
+/// consecutive slices of `sentence`
+fn collapse_punctuation<'a>(sentence: &'a str, words: Vec<&'a str>) -> Vec<&'a str> {
+    let class = |word: &str| {
+        let mut chars = word.chars();
+        match (chars.next(), chars.next()) {
+            (Some(ch), None) => punctuation_class(ch),
+            _ => None,
+        }
+    };
+    merge_runs(sentence, words, class)
+        .into_iter()
+        .map(|(word, _)| word)
+        .collect()
+}
+
+/// Merge the opening quotes and brackets into the next word and the closing ones into the
+/// previous word, `words` being consecutive slices of `sentence`
+fn attach_punctuation<'a>(sentence: &'a str, words: Vec<&'a str>) -> Vec<&'a str> {
+    let is_opening = |word: &str| word.chars().count() == 1 && "“‘「『（《【(".contains(word);
+    let is_closing = |word: &str| word.chars().count() == 1 && "”’」』）》】)".contains(word);
+    let has_alphanumeric = |word: &str| word.chars().any(char::is_alphanumeric);
+
+    let mut attached: Vec<&'a str> = Vec::with_capacity(words.len());
+    let mut opening: Vec<&'a str> = Vec::new();
+    for word in words {
+        if is_opening(word) {
+            opening.push(word);
+        } else if has_alphanumeric(word) {
+            let first = opening.first().copied().unwrap_or(word);
+            attached.push(join_words(sentence, first, word));
+            opening.clear();
+        } else {
+            attached.append(&mut opening);
+            match attached.last_mut() {
+                Some(prev) if is_closing(word) && has_alphanumeric(prev) => {
+                    *prev = join_words(sentence, prev, word);
+                }
+                _ => attached.push(word),
+            }
+        }
+    }
+    attached.append(&mut opening);
+    attached
+}
+
+/// ASCII chars which are kept together in one word when they aren't part of a dict word
+///
+/// Runs of ASCII letters, digits and the symbols `+`, `#`, `&`, `.`, `_` and `%` are merged into a
+/// single word, e.g. `iPhone13`, `v2.0`, `C++` or `50%`. A run made only of symbols is not merged.
+#[inline]
+fn is_ascii_word_byte(b: u8) -> bool {
+    b.is_ascii_alphanumeric() || b"+#&._%".contains(&b)
+}
+
+/// Fullwidth forms of the chars kept together by [`is_ascii_word_byte`], plus `－`
+///
+/// The default han regex doesn't match the fullwidth block U+FF01–FF5E, which also holds the
+/// common punctuation `，！？（）：；`. Matching it would route that punctuation through the dict,
+/// and since the dict has no fullwidth words the letters would still be cut one by one. Runs of
+/// these chars are kept together outside of han blocks instead, so product codes like "ＳＫ－ＩＩ"
+/// stay one word.
+#[inline]
+fn is_fullwidth_word_char(ch: char) -> bool {
+    matches!(ch, '０'..='９' | 'Ａ'..='Ｚ' | 'ａ'..='ｚ' | '＋' | '＃' | '＆' | '．' | '＿' | '％' | '－')
+}
+
+/// Whether `word` is a run of fullwidth letters, digits and symbols which
+/// [`push_fullwidth_runs`] keeps whole
+#[inline]
+fn is_fullwidth_word(word: &str) -> bool {
+    word.chars().all(is_fullwidth_word_char) && word.chars().any(char::is_alphanumeric)
+}
+
+/// Push `word` split into single chars like [`push_chars`], except for the runs of fullwidth
+/// letters, digits and symbols with at least one letter or digit, which are pushed whole
+fn push_fullwidth_runs<'a, W: WordSink<'a>>(word: &'a str, words: &mut W) {
+    let mut rest = word;
+    while !rest.is_empty() {
+        let run_len: usize = rest
+            .chars()
+            .take_while(|&ch| is_fullwidth_word_char(ch))
+            .map(char::len_utf8)
+            .sum();
+        if rest[..run_len].chars().any(char::is_alphanumeric) {
+            words.push_word(&rest[..run_len], TokenSource::Fullwidth, 0);
+            rest = &rest[run_len..];
+        } else {
+            let other_len = rest
+                .char_indices()
+                .skip(1)
+                .find(|&(_, ch)| is_fullwidth_word_char(ch))
+                .map_or(rest.len(), |(byte_index, _)| byte_index);
+            push_chars(&rest[..other_len], words);
+            rest = &rest[other_len..];
+        }
+    }
+}
+
+/// Whether a word spanning bytes `start..end` crosses one of the sorted byte offsets `hints`
+#[inline]
+fn crosses_hint(hints: &[usize], start: usize, end: usize) -> bool {
+    if hints.is_empty() {
+        return false;
+    }
+    let i = match hints.binary_search(&start) {
+        Ok(i) => i + 1,
+        Err(i) => i,
+    };
+    matches!(hints.get(i), Some(&hint) if hint < end)
+}
+
+#[inline]
+fn has_ascii_alphanumeric(word: &str) -> bool {
+    word.bytes().any(|b| b.is_ascii_alphanumeric())
+}
+
+/// Split the last whitespace separated field off `line`, unless it's the only one
+fn split_last_field(line: &str) -> Option<(&str, &str)> {
+    let split = line.rfind(char::is_whitespace)?;
+    let last = line[split..].trim_start();
+    let head = line[..split].trim_end();
+    if head.is_empty() {
+        None
+    } else {
+        Some((head, last))
+    }
+}
+
+/// Number of chars of `word` as counted in token offsets
+///
+/// With the `grapheme` feature these are grapheme clusters, otherwise Unicode scalar values.
+#[inline]
+fn char_width(word: &str) -> usize {
+    #[cfg(feature = "grapheme")]
+    {
+        word.graphemes(true).count()
+    }
+    #[cfg(not(feature = "grapheme"))]
+    {
+        word.chars().count()
+    }
+}
+
+/// Push `word` split into single chars, or grapheme clusters with the `grapheme` feature
+#[inline]
+fn push_chars<'a, W: WordSink<'a>>(word: &'a str, words: &mut W) {
+    #[cfg(feature = "grapheme")]
+    for grapheme in word.graphemes(true) {
+        words.push_word(grapheme, TokenSource::CharFallback, 0);
+    }
+    #[cfg(not(feature = "grapheme"))]
+    for (i, ch) in word.char_indices() {
+        words.push_word(&word[i..i + ch.len_utf8()], TokenSource::CharFallback, 0);
+    }
+}
+
+/// Receives the words cut from a sentence along with where they come from and their dict
+/// frequency, which is 0 for the words routing didn't take from the dict
+trait WordSink<'a> {
+    fn push_word(&mut self, word: &'a str, source: TokenSource, freq: usize);
+}
+
+impl<'a> WordSink<'a> for Vec<&'a str> {
+    #[inline]
+    fn push_word(&mut self, word: &'a str, _source: TokenSource, _freq: usize) {
+        self.push(word);
+    }
+}
+
+impl<'a> WordSink<'a> for Vec<(&'a str, usize)> {
+    #[inline]
+    fn push_word(&mut self, word: &'a str, _source: TokenSource, freq: usize) {
+        self.push((word, freq));
+    }
+}
+
+impl<'a> WordSink<'a> for Vec<(&'a str, TokenSource)> {
+    #[inline]
+    fn push_word(&mut self, word: &'a str, source: TokenSource, _freq: usize) {
+        self.push((word, source));
+    }
+}
+
+/// Source of a word picked by routing whose dict frequency is `freq`
+#[inline]
+fn routed_source(freq: usize) -> TokenSource {
+    if freq > 0 {
+        TokenSource::Dictionary
+    } else {
+        TokenSource::CharFallback
+    }
+}
+
+/// Push the char at `byte_start` of `sentence`, which routing picked as a single char
+#[inline]
+fn push_routed_char<'a, W: WordSink<'a>>(sentence: &'a str, byte_start: usize, route: &[RouteStep], words: &mut W) {
+    let (_, byte_end, freq) = route[byte_start];
+    words.push_word(&sentence[byte_start..byte_end], routed_source(freq), freq);
+}
+
+/// Order two route steps `(log prob, byte end)` by log probability
+///
+/// Equally likely steps are ordered by byte end, so the longer word wins a tie, and a NaN
+/// log probability ranks below every other. This keeps the chosen route independent of the
+/// order in which the DAG edges are visited.
+#[inline]
+fn cmp_route(x: &(f64, usize), y: &(f64, usize)) -> Ordering {
+    let by_prob = match (x.0.is_nan(), y.0.is_nan()) {
+        (true, true) => Ordering::Equal,
+        (true, false) => Ordering::Less,
+        (false, true) => Ordering::Greater,
+        (false, false) => x.0.partial_cmp(&y.0).unwrap(),
+    };
+    by_prob.then(x.1.cmp(&y.1))
 }
 
 struct SplitMatches<'r, 't> {
@@ -176,6 +529,70 @@ pub enum TokenizeMode {
     Search,
 }
 
+/// Which single chars [`CutOptions::merge_single_chars`](struct.CutOptions.html#method.merge_single_chars) merges
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum MergeSingleChars {
+    /// Don't merge single chars
+    None,
+    /// Merge runs of single CJK chars
+    Cjk,
+    /// Merge runs of single ASCII letters and digits
+    Ascii,
+    /// Merge runs of single CJK chars and runs of single ASCII letters and digits
+    Both,
+}
+
+/// Scores the words of a route for [`Jieba::cut_with_scorer`](struct.Jieba.html#method.cut_with_scorer)
+///
+/// Routing picks the route through a han block with the highest sum of word scores. A word is
+/// either a dict word with its frequency, or a single char which isn't in the dict with the
+/// fallback frequency, see [`JiebaBuilder::fallback_freq`](struct.JiebaBuilder.html#method.fallback_freq).
+/// Words with a zero frequency are never scored nor picked. Closures taking
+/// `(word, freq, total)` are scorers too.
+///
+/// ```rust
+/// use jieba_rs::Jieba;
+///
+/// let jieba = Jieba::new();
+/// // Log frequency like the default, with a bonus for every extra char of a word
+/// let scorer = |word: &str, freq: usize, total: usize| {
+///     (freq as f64).ln() - (total as f64).ln() + 2.0 * (word.chars().count() - 1) as f64
+/// };
+/// let words = jieba.cut_with_scorer("南京市长江大桥", &scorer, false);
+/// ```
+pub trait RouteScorer {
+    /// Score of `word`, of frequency `freq` in a dict whose frequencies add up to `total`
+    fn score(&self, word: &str, freq: usize, total: usize) -> f64;
+}
+
+impl<F: Fn(&str, usize, usize) -> f64> RouteScorer for F {
+    fn score(&self, word: &str, freq: usize, total: usize) -> f64 {
+        self(word, freq, total)
+    }
+}
+
+/// The scorer of [`Jieba::cut`](struct.Jieba.html#method.cut), the log probability
+/// `ln(freq) - ln(total)` of a word
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct LogFreqScorer;
+
+impl RouteScorer for LogFreqScorer {
+    fn score(&self, _word: &str, freq: usize, total: usize) -> f64 {
+        (freq as f64).ln() - (total as f64).ln()
+    }
+}
+
+/// Adjustments of the routing of `Jieba::calc`
+#[derive(Clone, Copy, Default)]
+struct RouteParams<'r> {
+    /// Sorted byte offsets of the soft boundaries, see `cut_with_boundary_hints`
+    hints: &'r [usize],
+    /// Scorer replacing the log frequency of the words
+    scorer: Option<&'r dyn RouteScorer>,
+    /// Filter of the words longer than one char, see `cut_restricted`
+    allowed: Option<&'r dyn Fn(&str) -> bool>,
+}
+
 /// A Token
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub struct Token<'a> {
@@ -187,7 +604,261 @@ pub struct Token<'a> {
     pub end: usize,
 }
 
+/// A word with its tag and position, returned by [`Jieba::analyze`](struct.Jieba.html#method.analyze)
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct AnalyzedToken<'a> {
+    /// Word of the token
+    pub word: &'a str,
+    /// Word tag
+    pub tag: &'a str,
+    /// Unicode start position of the token
+    pub start: usize,
+    /// Unicode end position of the token
+    pub end: usize,
+}
+
+/// Statistics of a segmented text, returned by [`Jieba::stats`](struct.Jieba.html#method.stats)
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct TextStats {
+    /// Number of words
+    pub tokens: usize,
+    /// Number of distinct words
+    pub distinct_words: usize,
+    /// Average number of chars of a word, 0 without any word
+    pub average_word_len: f64,
+    /// Proportion of the words which aren't in the dict, 0 without any word
+    pub oov_ratio: f64,
+}
+
+/// Where a word returned by [`Jieba::cut_with_source`](struct.Jieba.html#method.cut_with_source) comes from
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum TokenSource {
+    /// A word of the dict picked by routing
+    Dictionary,
+    /// A word joined by HMM from chars the dict couldn't join
+    Hmm,
+    /// A single char which isn't a dict word, like punctuation
+    CharFallback,
+    /// A run of ASCII letters, digits and `+#&._%` which isn't picked from the dict
+    Ascii,
+    /// A run of fullwidth letters, digits and symbols outside of the han blocks, like "ＳＫ－ＩＩ"
+    Fullwidth,
+    /// A separator matched by the skip regex, like whitespace
+    Skip,
+}
+
+/// Options for [`Jieba::cut_with_options`](struct.Jieba.html#method.cut_with_options)
+///
+/// The default options cut the same way as [`Jieba::cut`](struct.Jieba.html#method.cut)
+/// with HMM disabled.
+///
+/// ```rust
+/// use jieba_rs::CutOptions;
+///
+/// let options = CutOptions::default().hmm(true).keep_whitespace(false);
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CutOptions {
+    hmm: bool,
+    cut_all: bool,
+    search: bool,
+    keep_whitespace: bool,
+    lowercase_ascii: bool,
+    merge_ascii: bool,
+    keep_numbers: bool,
+    max_token_chars: Option<usize>,
+    ignore_ascii_case: bool,
+    search_full_word: bool,
+    max_match_chars: Option<usize>,
+    ascii_dict: bool,
+    attach_punctuation: bool,
+    merge_single_chars: MergeSingleChars,
+    collapse_punctuation: bool,
+}
+
+impl Default for CutOptions {
+    fn default() -> Self {
+        CutOptions {
+            hmm: false,
+            cut_all: false,
+            search: false,
+            keep_whitespace: true,
+            lowercase_ascii: false,
+            merge_ascii: true,
+            keep_numbers: false,
+            max_token_chars: None,
+            ignore_ascii_case: false,
+            search_full_word: true,
+            max_match_chars: None,
+            ascii_dict: true,
+            attach_punctuation: false,
+            merge_single_chars: MergeSingleChars::None,
+            collapse_punctuation: false,
+        }
+    }
+}
+
+impl CutOptions {
+    /// Enable HMM or not, disabled by default
+    ///
+    /// HMM is never used when cutting all possible words.
+    pub fn hmm(mut self, hmm: bool) -> Self {
+        self.hmm = hmm;
+        self
+    }
+
+    /// Return all possible words like [`Jieba::cut_all`](struct.Jieba.html#method.cut_all), disabled by default
+    ///
+    /// Takes precedence over search mode.
+    pub fn cut_all(mut self, cut_all: bool) -> Self {
+        self.cut_all = cut_all;
+        self
+    }
+
+    /// Cut in search mode like [`Jieba::cut_for_search`](struct.Jieba.html#method.cut_for_search), disabled by default
+    pub fn search(mut self, search: bool) -> Self {
+        self.search = search;
+        self
+    }
+
+    /// In search mode, also return the words which are split into sub-words, enabled by default
+    ///
+    /// When disabled, a word with dict sub-words of 2 or 3 chars is replaced by them, while
+    /// words without sub-words are still returned. "中国科学院计算所" is cut into "中国", "科学",
+    /// "学院", "科学院", "中国科学院", "计算" and "计算所" when enabled, but "中国", "科学", "学院",
+    /// "科学院" and "计算" when disabled; chars which aren't part of any sub-word, like "所", aren't
+    /// returned.
+    pub fn search_full_word(mut self, search_full_word: bool) -> Self {
+        self.search_full_word = search_full_word;
+        self
+    }
+
+    /// Keep the words made of whitespaces only, enabled by default
+    ///
+    /// Every whitespace char is a word of its own, so whitespace-only text is cut into one word
+    /// per char when enabled and into no words at all when disabled.
+    pub fn keep_whitespace(mut self, keep_whitespace: bool) -> Self {
+        self.keep_whitespace = keep_whitespace;
+        self
+    }
+
+    /// Lowercase ASCII letters before looking words up in the dict, disabled by default
+    ///
+    /// The returned words keep the casing of the input text. Only dict entries written in
+    /// lowercase can be matched this way, so "Rust" and "RUST" both match a "rust" entry.
+    pub fn lowercase_ascii(mut self, lowercase_ascii: bool) -> Self {
+        self.lowercase_ascii = lowercase_ascii;
+        self
+    }
+
+    /// Match dict words regardless of the case of their ASCII letters, disabled by default
+    ///
+    /// Unlike [`lowercase_ascii`](#method.lowercase_ascii) this also matches dict entries with
+    /// uppercase letters, so "IPHONE" and "iphone" both match an "iPhone" entry. The returned
+    /// words keep the casing of the input text. When a lowercase entry and a mixed-case entry
+    /// only differ by case, the frequency of the lowercase entry is used.
+    pub fn ignore_ascii_case(mut self, ignore_ascii_case: bool) -> Self {
+        self.ignore_ascii_case = ignore_ascii_case;
+        self
+    }
+
+    /// Merge runs of single ASCII letters, digits and `+#&._%` into one word, enabled by default
+    ///
+    /// When disabled the words are left as the route through the dict cut them, so "a1b2" is
+    /// cut into "a", "1", "b" and "2". ASCII words in the dict, like "c#", are still matched as
+    /// a whole either way. Only applies without HMM, which has its own rules for ASCII text.
+    pub fn merge_ascii(mut self, merge_ascii: bool) -> Self {
+        self.merge_ascii = merge_ascii;
+        self
+    }
+
+    /// Route ASCII letters and digits through the dict, enabled by default
+    ///
+    /// When disabled, the dict words with ASCII letters or digits are ignored, so ASCII text is
+    /// only split by the regexes and by [`merge_ascii`](#method.merge_ascii): "Rustlang" stays
+    /// one word even if "Rust" and "lang" are dict words, and "T恤" is cut into "T" and "恤".
+    /// Doesn't apply when cutting all possible words.
+    pub fn ascii_dict(mut self, ascii_dict: bool) -> Self {
+        self.ascii_dict = ascii_dict;
+        self
+    }
+
+    /// Collapse runs of punctuation of the same class into one word, disabled by default
+    ///
+    /// Each punctuation char is its own class except for two groups: the periods and ellipses
+    /// `.。．…`, and the dashes `-‐–—―－`. So "——" and "。。。" each become one word, while "。，"
+    /// stays two. Whitespace, letters and digits are never collapsed. Doesn't apply in search
+    /// mode or when cutting all possible words, where words overlap.
+    pub fn collapse_punctuation(mut self, collapse_punctuation: bool) -> Self {
+        self.collapse_punctuation = collapse_punctuation;
+        self
+    }
+
+    /// Attach quotes and brackets to the word they enclose, disabled by default
+    ///
+    /// An opening mark `“‘「『（《【(` is merged into the word right after it and a closing mark
+    /// `”’」』）》】)` into the word right before it, so "公司（北京）" is cut into "公司" and
+    /// "（北京）" instead of "公司", "（", "北京" and "）". Only words with a letter or digit take
+    /// marks, a mark next to whitespace or other punctuation is left alone. Doesn't apply in
+    /// search mode or when cutting all possible words, where words overlap.
+    pub fn attach_punctuation(mut self, attach_punctuation: bool) -> Self {
+        self.attach_punctuation = attach_punctuation;
+        self
+    }
+
+    /// Merge the runs of single chars which aren't dict words into one word, none by default
+    ///
+    /// `kind` selects whether runs of CJK chars, of ASCII letters and digits, or both are
+    /// merged; a run never mixes CJK and ASCII chars. This is applied after cutting, so with
+    /// HMM it only merges the chars HMM left on their own, overriding its decision. Unlike
+    /// [`merge_ascii`](#method.merge_ascii) it also applies with HMM, but ASCII letters and
+    /// digits which are dict words on their own aren't merged. Doesn't apply in search mode or
+    /// when cutting all possible words, where words overlap.
+    pub fn merge_single_chars(mut self, kind: MergeSingleChars) -> Self {
+        self.merge_single_chars = kind;
+        self
+    }
+
+    /// Keep numeric expressions as single words, disabled by default
+    ///
+    /// A numeric expression is either ASCII digits with an optional decimal part followed by any
+    /// of `十百千万亿`, like "4.3亿", or at least two Chinese numerals `零〇一二两三四五六七八九十百千万亿`
+    /// starting with one of `零〇一二两三四五六七八九十`, like "两千零五". It may be followed by one
+    /// of the units `元年月日号岁个次倍%`. ASCII digits which are part of a longer ASCII word, like
+    /// in "iPhone13", are left alone. Doesn't apply when cutting all possible words.
+    pub fn keep_numbers(mut self, keep_numbers: bool) -> Self {
+        self.keep_numbers = keep_numbers;
+        self
+    }
+
+    /// Only consider the dict words of at most `max_match_chars` chars when routing, unlimited by default
+    ///
+    /// Every position of the text is a candidate start of each dict word starting there, so long
+    /// runs of chars which start many dict words make routing slow. Bounding the length of the
+    /// candidates bounds the work done per char. Unlike [`max_token_chars`](#method.max_token_chars),
+    /// which cuts long words again after routing, the longer dict words are never matched at
+    /// all. The output only changes if a word over the bound would have been picked, so a bound
+    /// of at least the length of the longest dict word never changes it. A bound of 0 is treated
+    /// as 1.
+    pub fn max_match_chars(mut self, max_match_chars: Option<usize>) -> Self {
+        self.max_match_chars = max_match_chars;
+        self
+    }
+
+    /// Split the words longer than `max_token_chars` chars, unlimited by default
+    ///
+    /// A word over the limit is cut again along the most likely route made of dict words and
+    /// single chars which fit in the limit, so "中华人民共和国" becomes "中华", "人民" and "共和国"
+    /// with a limit of 3. A limit of 0 is treated as 1.
+    pub fn max_token_chars(mut self, max_token_chars: Option<usize>) -> Self {
+        self.max_token_chars = max_token_chars;
+        self
+    }
+}
+
 /// A tagged word
+///
+/// Displayed as `word/tag`, like the output of Python jieba's `posseg`.
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub struct Tag<'a> {
     /// Word
@@ -196,6 +867,12 @@ pub struct Tag<'a> {
     pub tag: &'a str,
 }
 
+impl<'a> fmt::Display for Tag<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}/{}", self.word, self.tag)
+    }
+}
+
 #[derive(Debug, Clone)]
 struct Record {
     word: String,
@@ -215,697 +892,4630 @@ impl Record {
 pub struct Jieba {
     records: Vec<Record>,
     cedar: Cedar,
+    /// ASCII-lowercased keys of the words with uppercase ASCII letters, for case-insensitive matching
+    cedar_folded: Cedar,
     total: usize,
+    /// Upper bound of the frequencies in the dict, exact except after lowering the frequency of a
+    /// word with `add_word`, which doesn't recompute it; `del_word` recomputes it when deleting
+    /// the word with the highest frequency
+    max_freq: usize,
     longest_word_len: usize,
+    re_han: Regex,
+    re_skip: Regex,
+    re_han_cut_all: Regex,
+    re_skip_cut_all: Regex,
+    hmm_forbidden: HashSet<String>,
+    force_split: HashSet<String>,
+    /// `ln(weight)` of the words given a weight other than 1 by `set_word_weight`
+    log_weights: HashMap<String, f64>,
+    /// Frequency routing gives to the single chars which aren't in the dict
+    fallback_freq: usize,
 }
 
-#[cfg(feature = "default-dict")]
-impl Default for Jieba {
+/// Builder of [`Jieba`](struct.Jieba.html) instances with custom settings
+///
+/// Text is first split into blocks matched by the han regex, which are cut with the dict,
+/// and the blocks in between, which are further split by the skip regex. Words never span
+/// several blocks, hence the han regex must not match any separator the skip regex is meant
+/// to split on, otherwise separators end up inside words.
+///
+/// ```rust
+/// use jieba_rs::JiebaBuilder;
+/// use regex::Regex;
+///
+/// let jieba = JiebaBuilder::new()
+///     .han_regex(Regex::new(r"([\u{4E00}-\u{9FFF}a-zA-Z0-9+#&\._%@]+)").unwrap())
+///     .build_empty();
+/// ```
+///
+/// The dict is the embed dict unless another one is set with [dict](#method.dict):
+///
+/// ```rust
+/// use jieba_rs::Jieba;
+///
+/// let mut dict = "北京 100 ns\n烤鸭 100 n".as_bytes();
+/// let jieba = Jieba::builder().dict(&mut dict).try_build().unwrap();
+/// assert_eq!(jieba.cut("北京烤鸭", false), vec!["北京", "烤鸭"]);
+/// ```
+#[derive(Debug, Clone)]
+pub struct JiebaBuilder {
+    re_han: Regex,
+    re_skip: Regex,
+    dict: DictSource,
+    fallback_freq: usize,
+}
+
+/// Dict an instance is built with
+#[derive(Debug, Clone)]
+enum DictSource {
+    /// The embed dict, or an empty dict without the `default-dict` feature
+    Default,
+    Text(String),
+    /// Reading the dict failed, the error is returned when building
+    ReadError(io::ErrorKind, String),
+}
+
+impl Default for JiebaBuilder {
     fn default() -> Self {
-        Jieba::new()
+        JiebaBuilder {
+            re_han: RE_HAN_DEFAULT.clone(),
+            re_skip: RE_SKIP_DEAFULT.clone(),
+            dict: DictSource::Default,
+            fallback_freq: 1,
+        }
     }
 }
 
-impl Jieba {
-    /// Create a new instance with empty dict
-    pub fn empty() -> Self {
+impl JiebaBuilder {
+    /// Create a new builder with the default settings
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Regex matching the blocks of text cut with the dict
+    ///
+    /// Defaults to CJK characters, ASCII letters, digits and `+#&._%`. Fullwidth letters and
+    /// digits aren't matched by default, runs of them with `＋＃＆．＿％－` are kept as one word
+    /// outside of han blocks, like the product code "ＳＫ－ＩＩ".
+    pub fn han_regex(mut self, re_han: Regex) -> Self {
+        self.re_han = re_han;
+        self
+    }
+
+    /// Regex matching the separators in the text outside of han blocks
+    ///
+    /// Matches are returned as words, the remaining text is split into single characters.
+    /// Defaults to `\r\n` and whitespaces.
+    pub fn skip_regex(mut self, re_skip: Regex) -> Self {
+        self.re_skip = re_skip;
+        self
+    }
+
+    /// Use `\r\n`, whitespaces and each of `separators` as the skip regex
+    ///
+    /// A shortcut for [skip_regex](#method.skip_regex) which makes every separator a word of
+    /// its own. Separators matched by the han regex, like the ASCII letters, digits and `+#&._%`
+    /// matched by default, are cut with the dict instead, so they have to be left out of the han
+    /// regex too.
+    pub fn separators(self, separators: &[char]) -> Self {
+        let class: String = separators.iter().map(|ch| regex::escape(&ch.to_string())).collect();
+        let re_skip = if class.is_empty() {
+            RE_SKIP_DEAFULT.clone()
+        } else {
+            Regex::new(&format!(r"(\r\n|\s|[{}])", class)).unwrap()
+        };
+        self.skip_regex(re_skip)
+    }
+
+    /// Frequency given to the single chars which aren't in the dict when routing, 1 by default
+    ///
+    /// Routing picks the most likely way to cut a block of text, a char which isn't in the dict
+    /// being as likely as a word of this frequency. Raising it makes the routes through unknown
+    /// chars more likely, so the router rather leaves chars on their own than joins them into
+    /// longer dict words. A frequency of 0 is treated as 1.
+    pub fn fallback_freq(mut self, fallback_freq: usize) -> Self {
+        self.fallback_freq = fallback_freq.max(1);
+        self
+    }
+
+    /// Read the dict to build the instance with instead of the embed dict
+    ///
+    /// The dict is read right away, an error reading it is returned by
+    /// [try_build](#method.try_build).
+    pub fn dict<R: BufRead>(mut self, dict: &mut R) -> Self {
+        let mut text = String::new();
+        self.dict = match dict.read_to_string(&mut text) {
+            Ok(_) => DictSource::Text(text),
+            Err(err) => DictSource::ReadError(err.kind(), err.to_string()),
+        };
+        self
+    }
+
+    /// Build an instance with the dict set by [dict](#method.dict), or the embed dict
+    ///
+    /// Without a dict set and without the `default-dict` feature, the dict is empty.
+    pub fn try_build(mut self) -> Result<Jieba, Error> {
+        match std::mem::replace(&mut self.dict, DictSource::Default) {
+            #[cfg(feature = "default-dict")]
+            DictSource::Default => self.build_with_dict_str(DEFAULT_DICT),
+            #[cfg(not(feature = "default-dict"))]
+            DictSource::Default => Ok(self.build_empty()),
+            DictSource::Text(text) => self.build_with_dict_str(&text),
+            DictSource::ReadError(kind, message) => Err(io::Error::new(kind, message).into()),
+        }
+    }
+
+    /// Build an instance with the dict parsed from `dict`, ignoring the dict set by
+    /// [dict](#method.dict)
+    fn build_with_dict_str(self, dict: &str) -> Result<Jieba, Error> {
+        let mut instance = self.build_empty();
+        instance.load_dict_str(dict)?;
+        Ok(instance)
+    }
+
+    /// Build an instance with empty dict, ignoring the dict set by [dict](#method.dict)
+    pub fn build_empty(self) -> Jieba {
         Jieba {
             records: Vec::new(),
             cedar: Cedar::new(),
+            cedar_folded: Cedar::new(),
             total: 0,
+            max_freq: 0,
             longest_word_len: 0,
+            re_han: self.re_han,
+            re_skip: self.re_skip,
+            re_han_cut_all: RE_HAN_CUT_ALL.clone(),
+            re_skip_cut_all: RE_SKIP_CUT_ALL.clone(),
+            hmm_forbidden: HashSet::new(),
+            force_split: HashSet::new(),
+            log_weights: HashMap::new(),
+            fallback_freq: self.fallback_freq,
         }
     }
 
-    /// Create a new instance with embed dict
+    /// Build an instance like [try_build](#method.try_build), with embed dict unless another
+    /// dict is set
     ///
-    /// Requires `default-dict` feature to be enabled.
-    #[cfg(feature = "default-dict")]
-    pub fn new() -> Self {
-        let mut instance = Self::empty();
-        let mut default_dict = io::BufReader::new(DEFAULT_DICT.as_bytes());
-        instance.load_dict(&mut default_dict).unwrap();
-        instance
+    /// ## Panics
+    ///
+    /// Panics if the dict set by [dict](#method.dict) can't be read or loaded.
+    pub fn build(self) -> Jieba {
+        self.try_build().expect("the dict is well-formed")
     }
 
-    /// Create a new instance with dict
-    pub fn with_dict<R: BufRead>(dict: &mut R) -> Result<Self, Error> {
-        let mut instance = Self::empty();
-        instance.load_dict(dict)?;
-        Ok(instance)
+    /// Build an instance with dict
+    pub fn build_with_dict<R: BufRead>(self, dict: &mut R) -> Result<Jieba, Error> {
+        self.dict(dict).try_build()
     }
-    /// Add word to dict, return `freq`
-    ///
-    /// `freq`: if `None`, will be given by [suggest_freq](#method.suggest_freq)
-    ///
-    /// `tag`: if `None`, will be given `""`
-    pub fn add_word(&mut self, word: &str, freq: Option<usize>, tag: Option<&str>) -> usize {
-        let freq = freq.unwrap_or_else(|| self.suggest_freq(word));
-        let tag = tag.unwrap_or("");
+}
 
-        match self.cedar.exact_match_search(word) {
-            Some((word_id, _, _)) => {
-                let old_freq = self.records[word_id as usize].freq;
-                self.records[word_id as usize].freq = freq;
-                self.total += freq - old_freq;
-            }
-            None => {
-                self.records
-                    .push(Record::new(String::from(word), freq, String::from(tag)));
-                let word_id = (self.records.len() - 1) as i32;
+/// Reusable scratch buffers for [`Jieba::cut_into`](struct.Jieba.html#method.cut_into)
+///
+/// A context holds the output words as well as the DAG, route and HMM buffers used while
+/// cutting, so that cutting many sentences in a loop doesn't allocate for every call.
+///
+/// The words kept in the context borrow from the sentences passed to `cut_into`, so every
+/// sentence cut with a `CutContext<'a>` must outlive `'a`. Each call to `cut_into` clears the
+/// words of the previous call, hence the returned slice can only be used until the context
+/// is reused.
+#[derive(Debug, Default)]
+pub struct CutContext<'a> {
+    words: Vec<&'a str>,
+    buffers: RouteBuffers<'a>,
+}
 
-                self.cedar.update(word, word_id);
-                self.total += freq;
-            }
-        };
+impl<'a> CutContext<'a> {
+    /// Create a new empty context
+    pub fn new() -> Self {
+        Self::default()
+    }
 
-        let curr_word_len = word.chars().count();
-        if self.longest_word_len < curr_word_len {
-            self.longest_word_len = curr_word_len;
+    /// Create a new context with buffers sized for sentences of about `capacity` bytes
+    pub fn with_capacity(capacity: usize) -> Self {
+        CutContext {
+            words: Vec::with_capacity(capacity),
+            buffers: RouteBuffers::with_capacity(capacity),
         }
-
-        freq
     }
+}
 
-    /// Load dictionary
-    pub fn load_dict<R: BufRead>(&mut self, dict: &mut R) -> Result<(), Error> {
-        let mut buf = String::new();
-        self.total = 0;
-        self.longest_word_len = 0;
+/// `(log prob, byte end, dict freq)` of the best route from a byte offset of a han block, the
+/// dict freq of the word up to the byte end being 0 when it isn't a dict word
+type RouteStep = (f64, usize, usize);
 
-        while dict.read_line(&mut buf)? > 0 {
-            {
-                let parts: Vec<&str> = buf.trim().split_whitespace().collect();
-                if parts.is_empty() {
-                    // Skip empty lines
-                    continue;
-                }
+/// The DAG, route and HMM buffers used while routing the han blocks
+#[derive(Debug, Default)]
+struct RouteBuffers<'a> {
+    route: Vec<RouteStep>,
+    dag: StaticSparseDAG,
+    v: Vec<f64>,
+    prev: Vec<Option<hmm::Status>>,
+    path: Vec<hmm::Status>,
+    /// Words cut by HMM, before they're checked against `hmm_forbidden` and passed on
+    hmm_words: Vec<&'a str>,
+}
 
-                let word = parts[0];
-                let freq = parts
-                    .get(1)
-                    .map(|x| {
-                        x.parse::<usize>()
-                            .map_err(|e| Error::InvalidDictEntry(format!("{}", e)))
-                    })
-                    .unwrap_or(Ok(0))?;
-                let tag = parts.get(2).cloned().unwrap_or("");
-
-                let curr_word_len = word.chars().count();
-                if self.longest_word_len < curr_word_len {
-                    self.longest_word_len = curr_word_len;
-                }
-
-                match self.cedar.exact_match_search(word) {
-                    Some((word_id, _, _)) => {
-                        self.records[word_id as usize].freq = freq;
-                    }
-                    None => {
-                        self.records
-                            .push(Record::new(String::from(word), freq, String::from(tag)));
-                        let word_id = (self.records.len() - 1) as i32;
-                        self.cedar.update(word, word_id);
-                    }
-                };
-            }
-            buf.clear();
+impl<'a> RouteBuffers<'a> {
+    fn with_capacity(capacity: usize) -> Self {
+        RouteBuffers {
+            route: Vec::with_capacity(capacity),
+            dag: StaticSparseDAG::with_size_hint(capacity),
+            v: Vec::new(),
+            prev: Vec::new(),
+            path: Vec::new(),
+            hmm_words: Vec::new(),
         }
-        self.total = self.records.iter().map(|n| n.freq).sum();
+    }
+}
 
-        Ok(())
+/// The words of many sentences stored as spans in one buffer, filled by
+/// [`Jieba::cut_into_arena`](struct.Jieba.html#method.cut_into_arena)
+///
+/// Every word is a `(sentence_id, byte_start, byte_end)` span into the sentence it was cut from,
+/// `sentence_id` being the order in which the sentences were cut. Words are turned into `&str`
+/// on demand. The arena also keeps the cut buffers, so bulk cutting neither allocates per
+/// sentence nor per word once the buffers have grown.
+///
+/// ```rust
+/// use jieba_rs::{Jieba, SegmentArena};
+///
+/// let jieba = Jieba::new();
+/// let mut arena = SegmentArena::new();
+/// for sentence in &["北京烤鸭", "我们中出了一个叛徒"] {
+///     jieba.cut_into_arena(sentence, false, &mut arena);
+/// }
+/// assert_eq!(arena.word(0), "北京烤鸭");
+/// assert_eq!(arena.spans()[1], (1, 0, 6));
+/// ```
+#[derive(Debug, Default)]
+pub struct SegmentArena<'a> {
+    sentences: Vec<&'a str>,
+    spans: Vec<(usize, usize, usize)>,
+    ctx: CutContext<'a>,
+}
+
+impl<'a> SegmentArena<'a> {
+    /// Create a new empty arena
+    pub fn new() -> Self {
+        Self::default()
     }
 
-    fn get_word_freq(&self, word: &str, default: usize) -> usize {
-        match self.cedar.exact_match_search(word) {
-            Some((word_id, _, _)) => self.records[word_id as usize].freq,
-            _ => default,
-        }
+    /// Number of words in the arena
+    pub fn len(&self) -> usize {
+        self.spans.len()
     }
 
-    /// Suggest word frequency to force the characters in a word to be joined or splitted.
-    pub fn suggest_freq(&self, segment: &str) -> usize {
-        let logtotal = (self.total as f64).ln();
-        let logfreq = self.cut(segment, false).iter().fold(0f64, |freq, word| {
-            freq + (self.get_word_freq(word, 1) as f64).ln() - logtotal
-        });
-        std::cmp::max((logfreq + logtotal).exp() as usize + 1, self.get_word_freq(segment, 1))
+    /// Whether the arena has no words
+    pub fn is_empty(&self) -> bool {
+        self.spans.is_empty()
     }
 
-    #[allow(clippy::ptr_arg)]
-    fn calc(&self, sentence: &str, dag: &StaticSparseDAG, route: &mut Vec<(f64, usize)>) {
-        let str_len = sentence.len();
+    /// The `(sentence_id, byte_start, byte_end)` spans of all the words, in cut order
+    pub fn spans(&self) -> &[(usize, usize, usize)] {
+        &self.spans
+    }
 
-        if str_len + 1 > route.len() {
-            route.resize(str_len + 1, (0.0, 0));
-        }
+    /// The sentence with id `sentence_id`
+    ///
+    /// Panics if `sentence_id` is out of bounds.
+    pub fn sentence(&self, sentence_id: usize) -> &'a str {
+        self.sentences[sentence_id]
+    }
 
-        let logtotal = (self.total as f64).ln();
-        let mut prev_byte_start = str_len;
-        let curr = sentence.char_indices().map(|x| x.0).rev();
-        for byte_start in curr {
-            let pair = dag
-                .iter_edges(byte_start)
-                .map(|byte_end| {
-                    let wfrag = if byte_end == str_len {
-                        &sentence[byte_start..]
-                    } else {
-                        &sentence[byte_start..byte_end]
-                    };
+    /// The word at `index`
+    ///
+    /// Panics if `index` is out of bounds.
+    pub fn word(&self, index: usize) -> &'a str {
+        let (sentence_id, byte_start, byte_end) = self.spans[index];
+        &self.sentences[sentence_id][byte_start..byte_end]
+    }
 
-                    let freq = if let Some((word_id, _, _)) = self.cedar.exact_match_search(wfrag) {
-                        self.records[word_id as usize].freq
-                    } else {
-                        1
-                    };
+    /// Iterate over all the words, in cut order
+    pub fn words(&self) -> impl Iterator<Item = &'a str> + '_ {
+        self.spans
+            .iter()
+            .map(move |&(sentence_id, byte_start, byte_end)| &self.sentences[sentence_id][byte_start..byte_end])
+    }
 
-                    ((freq as f64).ln() - logtotal + route[byte_end].0, byte_end)
-                })
-                .max_by(|x, y| x.partial_cmp(y).unwrap_or(Ordering::Equal));
+    /// Remove all the sentences and words, keeping the allocated buffers
+    pub fn clear(&mut self) {
+        self.sentences.clear();
+        self.spans.clear();
+    }
+}
 
-            if let Some(p) = pair {
-                route[byte_start] = p;
-            } else {
-                let byte_end = prev_byte_start;
-                let freq = 1;
-                route[byte_start] = ((freq as f64).ln() - logtotal + route[byte_end].0, byte_end);
-            }
+impl FromIterator<(String, usize, String)> for Jieba {
+    fn from_iter<I: IntoIterator<Item = (String, usize, String)>>(iter: I) -> Self {
+        let mut instance = Jieba::empty();
+        instance.extend(iter);
+        instance
+    }
+}
 
-            prev_byte_start = byte_start;
+impl Extend<(String, usize, String)> for Jieba {
+    /// Load `(word, freq, tag)` dict entries like [`load_dict`](struct.Jieba.html#method.load_dict) does
+    fn extend<I: IntoIterator<Item = (String, usize, String)>>(&mut self, iter: I) {
+        for (word, freq, tag) in iter {
+            self.insert_record(&word, freq, &tag);
         }
+        self.total = self.records.iter().map(|n| n.freq).sum();
     }
+}
 
-    fn dag(&self, sentence: &str, dag: &mut StaticSparseDAG) {
-        let mut iter = sentence.char_indices().peekable();
-        while let Some((byte_start, _)) = iter.next() {
-            dag.start(byte_start);
-            let haystack = &sentence[byte_start..];
+/// Number of bytes [`Jieba::cut_reader`](struct.Jieba.html#method.cut_reader) reads at once
+const CUT_READER_CHUNK_LEN: usize = 8 * 1024;
 
-            for (_, end_index) in self.cedar.common_prefix_iter(haystack) {
-                dag.insert(end_index + byte_start + 1);
+/// Iterator over the words of a reader, see [`Jieba::cut_reader`](struct.Jieba.html#method.cut_reader)
+struct CutReader<'j, R> {
+    jieba: &'j Jieba,
+    reader: R,
+    hmm: bool,
+    /// Bytes read but not cut yet, starting at a han block or at the start of the text
+    buf: Vec<u8>,
+    words: VecDeque<String>,
+    done: bool,
+}
+
+impl<'j, R: BufRead> CutReader<'j, R> {
+    /// Read the next chunk, then cut the buffered text up to the start of its last han block,
+    /// which may go on in the next chunk, or all of it at the end of the text
+    fn read_chunk(&mut self) -> io::Result<()> {
+        let mut chunk = [0; CUT_READER_CHUNK_LEN];
+        let len = loop {
+            match self.reader.read(&mut chunk) {
+                Ok(len) => break len,
+                Err(err) if err.kind() == io::ErrorKind::Interrupted => continue,
+                Err(err) => return Err(err),
             }
+        };
 
-            dag.commit();
+        if len == 0 {
+            self.done = true;
+            let text = std::str::from_utf8(&self.buf).map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+            let words = self.jieba.cut(text, self.hmm);
+            self.words.extend(words.into_iter().map(String::from));
+            self.buf.clear();
+            return Ok(());
         }
-    }
 
-    fn cut_all_internal<'a>(&self, sentence: &'a str, words: &mut Vec<&'a str>) {
-        let str_len = sentence.len();
-        let mut dag = StaticSparseDAG::with_size_hint(sentence.len());
-        self.dag(sentence, &mut dag);
+        self.buf.extend_from_slice(&chunk[..len]);
+        // A char may be split across chunks, only its first bytes are read so far
+        let valid_len = match std::str::from_utf8(&self.buf) {
+            Ok(text) => text.len(),
+            Err(err) if err.error_len().is_none() => err.valid_up_to(),
+            Err(err) => return Err(io::Error::new(io::ErrorKind::InvalidData, err)),
+        };
+        let text = std::str::from_utf8(&self.buf[..valid_len]).expect("valid UTF-8 prefix");
+        let boundary = self.jieba.re_han.find_iter(text).last().map_or(0, |m| m.start());
+        if boundary > 0 {
+            let words = self.jieba.cut(&text[..boundary], self.hmm);
+            self.words.extend(words.into_iter().map(String::from));
+            self.buf.drain(..boundary);
+        }
+        Ok(())
+    }
+}
 
-        let curr = sentence.char_indices().map(|x| x.0);
-        for byte_start in curr {
-            for byte_end in dag.iter_edges(byte_start) {
-                let word = if byte_end == str_len {
-                    &sentence[byte_start..]
-                } else {
-                    &sentence[byte_start..byte_end]
-                };
+impl<'j, R: BufRead> Iterator for CutReader<'j, R> {
+    type Item = io::Result<String>;
 
-                words.push(word)
+    fn next(&mut self) -> Option<Self::Item> {
+        while self.words.is_empty() && !self.done {
+            if let Err(err) = self.read_chunk() {
+                self.done = true;
+                return Some(Err(err));
             }
         }
+        self.words.pop_front().map(Ok)
     }
+}
 
-    fn cut_dag_no_hmm<'a>(
-        &self,
-        sentence: &'a str,
-        words: &mut Vec<&'a str>,
-        route: &mut Vec<(f64, usize)>,
-        dag: &mut StaticSparseDAG,
-    ) {
-        self.dag(sentence, dag);
-        self.calc(sentence, dag, route);
-        let mut x = 0;
-        let mut left: Option<usize> = None;
-
-        while x < sentence.len() {
-            let y = route[x].1;
-            let l_str = if y < sentence.len() {
-                &sentence[x..y]
-            } else {
-                &sentence[x..]
-            };
+#[cfg(feature = "default-dict")]
+impl Default for Jieba {
+    fn default() -> Self {
+        Jieba::new()
+    }
+}
 
-            if l_str.chars().count() == 1 && l_str.chars().all(|ch| ch.is_ascii_alphanumeric()) {
-                if left.is_none() {
-                    left = Some(x);
-                }
-            } else {
-                if let Some(byte_start) = left {
-                    let word = &sentence[byte_start..x];
-                    words.push(word);
-                    left = None;
-                }
+impl Jieba {
+    /// Create a builder to configure a new instance
+    pub fn builder() -> JiebaBuilder {
+        JiebaBuilder::new()
+    }
 
-                let word = if y < sentence.len() {
-                    &sentence[x..y]
-                } else {
-                    &sentence[x..]
-                };
+    /// Create a new instance with empty dict
+    pub fn empty() -> Self {
+        JiebaBuilder::new().build_empty()
+    }
 
-                words.push(word);
-            }
-            x = y;
-        }
+    /// Create a new instance with embed dict
+    ///
+    /// The embed dict is parsed from memory, no file I/O is involved, so this also works on
+    /// targets without a filesystem such as WASM.
+    ///
+    /// Requires `default-dict` feature to be enabled.
+    #[cfg(feature = "default-dict")]
+    pub fn new() -> Self {
+        JiebaBuilder::new().build()
+    }
 
-        if let Some(byte_start) = left {
-            let word = &sentence[byte_start..];
-            words.push(word);
-        }
+    /// Create a new instance with embed dict, returning an error instead of panicking if the
+    /// embed dict can't be loaded
+    ///
+    /// Requires `default-dict` feature to be enabled.
+    #[cfg(feature = "default-dict")]
+    pub fn try_new() -> Result<Self, Error> {
+        JiebaBuilder::new().try_build()
+    }
 
-        dag.clear();
-        route.clear();
+    /// Create a new instance with dict
+    pub fn with_dict<R: BufRead>(dict: &mut R) -> Result<Self, Error> {
+        JiebaBuilder::new().build_with_dict(dict)
     }
 
-    #[allow(non_snake_case)]
-    fn cut_dag_hmm<'a>(
-        &self,
-        sentence: &'a str,
-        words: &mut Vec<&'a str>,
-        route: &mut Vec<(f64, usize)>,
-        dag: &mut StaticSparseDAG,
-        V: &mut Vec<f64>,
-        prev: &mut Vec<Option<hmm::Status>>,
-        path: &mut Vec<hmm::Status>,
-    ) {
-        self.dag(sentence, dag);
-        self.calc(sentence, dag, route);
-        let mut x = 0;
-        let mut left: Option<usize> = None;
+    /// Create a new instance with a dict given as a string
+    pub fn with_dict_str(dict: &str) -> Result<Self, Error> {
+        JiebaBuilder::new().build_with_dict_str(dict)
+    }
 
-        while x < sentence.len() {
-            let y = route[x].1;
+    /// Create a new instance with the dict file at `path`, parsed through a memory map
+    ///
+    /// The file is mapped instead of being read into a buffer, which saves a copy of large
+    /// dicts. The words are still copied into the instance, so the map is dropped once the dict
+    /// is loaded and the instance doesn't keep the file open. The file must be UTF-8 and must
+    /// not be modified while it's loaded, which the memory map can't prevent.
+    ///
+    /// Requires the `mmap` feature.
+    #[cfg(feature = "mmap")]
+    pub fn from_mmap<P: AsRef<std::path::Path>>(path: P) -> Result<Self, Error> {
+        let file = std::fs::File::open(path)?;
+        // SAFETY: the map is only read while loading the dict and dropped right after, the
+        // caller is asked not to modify the file meanwhile
+        let map = unsafe { memmap2::Mmap::map(&file)? };
+        let dict = std::str::from_utf8(&map).map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+        Self::with_dict_str(dict)
+    }
 
-            if sentence[x..y].chars().count() == 1 {
-                if left.is_none() {
-                    left = Some(x);
-                }
-            } else {
-                if let Some(byte_start) = left {
-                    let byte_end = x;
-                    let word = if byte_end < sentence.len() {
-                        &sentence[byte_start..byte_end]
-                    } else {
-                        &sentence[byte_start..]
-                    };
+    /// Create a new instance from `(word, freq, tag)` dict entries
+    ///
+    /// Entries are handled like the lines of a dict file: a repeated word keeps its first tag and
+    /// takes the frequency of its last entry.
+    pub fn from_records<I: IntoIterator<Item = (String, usize, String)>>(records: I) -> Self {
+        records.into_iter().collect()
+    }
+    /// Add word to dict, return `freq`
+    ///
+    /// `freq`: if `None`, will be given by [suggest_freq](#method.suggest_freq)
+    ///
+    /// `tag`: if `None`, will be given `""`
+    pub fn add_word(&mut self, word: &str, freq: Option<usize>, tag: Option<&str>) -> usize {
+        let freq = freq.unwrap_or_else(|| self.suggest_freq(word));
+        let tag = tag.unwrap_or("");
+        self.max_freq = std::cmp::max(self.max_freq, freq);
 
-                    if word.chars().count() == 1 {
-                        words.push(word);
-                    } else if self.cedar.exact_match_search(word).is_none() {
-                        hmm::cut_with_allocated_memory(word, words, V, prev, path);
-                    } else {
-                        let mut word_indices = word.char_indices().map(|x| x.0).peekable();
-                        while let Some(byte_start) = word_indices.next() {
-                            if let Some(byte_end) = word_indices.peek() {
-                                words.push(&word[byte_start..*byte_end]);
-                            } else {
-                                words.push(&word[byte_start..]);
-                            }
-                        }
-                    }
-                    left = None;
-                }
-                let word = if y < sentence.len() {
-                    &sentence[x..y]
-                } else {
-                    &sentence[x..]
-                };
-                words.push(word);
+        match self.cedar.exact_match_search(word) {
+            Some((word_id, _, _)) => {
+                let old_freq = self.records[word_id as usize].freq;
+                self.records[word_id as usize].freq = freq;
+                self.total = self.total.saturating_add(freq) - old_freq;
             }
-            x = y;
-        }
-
-        if let Some(byte_start) = left {
-            let word = &sentence[byte_start..];
+            None => {
+                self.records
+                    .push(Record::new(String::from(word), freq, String::from(tag)));
+                let word_id = (self.records.len() - 1) as i32;
 
-            if word.chars().count() == 1 {
-                words.push(word);
-            } else if self.cedar.exact_match_search(word).is_none() {
-                hmm::cut(word, words);
-            } else {
-                let mut word_indices = word.char_indices().map(|x| x.0).peekable();
-                while let Some(byte_start) = word_indices.next() {
-                    if let Some(byte_end) = word_indices.peek() {
-                        words.push(&word[byte_start..*byte_end]);
-                    } else {
-                        words.push(&word[byte_start..]);
-                    }
-                }
+                self.cedar.update(word, word_id);
+                self.update_folded(word, word_id);
+                self.total = self.total.saturating_add(freq);
             }
+        };
+
+        let curr_word_len = word.chars().count();
+        if self.longest_word_len < curr_word_len {
+            self.longest_word_len = curr_word_len;
         }
 
-        dag.clear();
-        route.clear();
+        freq
     }
 
-    #[allow(non_snake_case)]
-    fn cut_internal<'a>(&self, sentence: &'a str, cut_all: bool, hmm: bool) -> Vec<&'a str> {
-        let heuristic_capacity = sentence.len() / 2;
-        let mut words = Vec::with_capacity(heuristic_capacity);
-        let re_han: &Regex = if cut_all { &*RE_HAN_CUT_ALL } else { &*RE_HAN_DEFAULT };
-        let re_skip: &Regex = if cut_all { &*RE_SKIP_CUT_ALL } else { &*RE_SKIP_DEAFULT };
-        let splitter = SplitMatches::new(&re_han, sentence);
-        let mut route = Vec::with_capacity(heuristic_capacity);
-        let mut dag = StaticSparseDAG::with_size_hint(heuristic_capacity);
-
-        let R = 4;
-        let C = sentence.chars().count();
-        let mut V = if hmm { vec![0.0; R * C] } else { Vec::new() };
-        let mut prev: Vec<Option<hmm::Status>> = if hmm { vec![None; R * C] } else { Vec::new() };
-        let mut path: Vec<hmm::Status> = if hmm { vec![hmm::Status::B; C] } else { Vec::new() };
+    /// Add word to dict with a raw corpus count instead of a frequency
+    ///
+    /// Frequencies in the dict are on the scale of the corpus the dict was built from, so a small
+    /// raw count would be lost next to them. The count is scaled so that a count of 1 weighs as
+    /// much as the average word in the dict, that is `freq = count * total / word count`. With an
+    /// empty dict the count is used as is.
+    ///
+    /// Returns the frequency the word was added with.
+    ///
+    /// ## Params
+    ///
+    /// `word`: word to add
+    ///
+    /// `count`: number of times the word was seen in a corpus
+    ///
+    /// `tag`: tag of the word, empty if `None`
+    pub fn add_word_with_count(&mut self, word: &str, count: usize, tag: Option<&str>) -> usize {
+        let average = if self.records.is_empty() {
+            1
+        } else {
+            std::cmp::max(self.total / self.records.len(), 1)
+        };
+        self.add_word(word, Some(count.saturating_mul(average)), tag)
+    }
 
-        for state in splitter {
-            match state {
-                SplitState::Matched(_) => {
-                    let block = state.into_str();
-                    assert!(!block.is_empty());
+    /// Delete word from dict, return whether it was in the dict
+    ///
+    /// The last word of the dict takes the place of the deleted one, so the order of
+    /// [words](#method.words) changes, in exchange for a single word being reindexed. The
+    /// longest word length isn't recomputed, so it may be too high afterwards; it's only ever
+    /// used as an upper bound.
+    pub fn del_word(&mut self, word: &str) -> bool {
+        let word_id = match self.cedar.exact_match_search(word) {
+            Some((word_id, _, _)) => word_id,
+            None => return false,
+        };
 
-                    if cut_all {
-                        self.cut_all_internal(block, &mut words);
-                    } else if hmm {
-                        self.cut_dag_hmm(block, &mut words, &mut route, &mut dag, &mut V, &mut prev, &mut path);
-                    } else {
-                        self.cut_dag_no_hmm(block, &mut words, &mut route, &mut dag);
-                    }
-                }
-                SplitState::Unmatched(_) => {
-                    let block = state.into_str();
-                    assert!(!block.is_empty());
+        self.cedar.erase(word);
+        self.erase_folded(word, word_id);
+        let record = self.records.swap_remove(word_id as usize);
+        self.total -= record.freq;
 
-                    let skip_splitter = SplitMatches::new(&re_skip, block);
-                    for skip_state in skip_splitter {
-                        let word = skip_state.into_str();
-                        if word.is_empty() {
-                            continue;
-                        }
-                        if cut_all || re_skip.is_match(word) {
-                            words.push(word);
-                        } else {
-                            let mut word_indices = word.char_indices().map(|x| x.0).peekable();
-                            while let Some(byte_start) = word_indices.next() {
-                                if let Some(byte_end) = word_indices.peek() {
-                                    words.push(&word[byte_start..*byte_end]);
-                                } else {
-                                    words.push(&word[byte_start..]);
-                                }
-                            }
-                        }
-                    }
-                }
+        // The last record took the place of the deleted one, point its keys to its new index
+        if let Some(moved) = self.records.get(word_id as usize) {
+            let moved_word = moved.word.clone();
+            let old_id = self.records.len() as i32;
+            self.cedar.update(&moved_word, word_id);
+            if self.folded_id(&moved_word) == Some(old_id) {
+                self.cedar_folded.update(&moved_word.to_ascii_lowercase(), word_id);
             }
         }
-        words
+
+        if record.freq == self.max_freq {
+            self.max_freq = self.records.iter().map(|record| record.freq).max().unwrap_or(0);
+        }
+
+        true
     }
 
-    /// Cut the input text
-    ///
-    /// ## Params
+    /// Restore the embed dict, dropping every word added, deleted or loaded since
     ///
-    /// `sentence`: input text
+    /// The embed dict is parsed once and cached, so resetting is much cheaper than creating a new
+    /// instance. The regexes and the words forbidden from HMM joins are kept.
     ///
-    /// `hmm`: enable HMM or not
-    pub fn cut<'a>(&self, sentence: &'a str, hmm: bool) -> Vec<&'a str> {
-        self.cut_internal(sentence, false, hmm)
+    /// Requires `default-dict` feature to be enabled.
+    #[cfg(feature = "default-dict")]
+    pub fn reset_dict(&mut self) {
+        let base = &*DEFAULT_JIEBA;
+        self.records = base.records.clone();
+        self.cedar = base.cedar.clone();
+        self.cedar_folded = base.cedar_folded.clone();
+        self.total = base.total;
+        self.max_freq = base.max_freq;
+        self.longest_word_len = base.longest_word_len;
     }
 
-    /// Cut the input text, return all possible words
+    /// Forbid HMM from joining the chars of `word` into one word
     ///
-    /// ## Params
+    /// When HMM recovers `word` from chars the dict cut apart, the chars are kept as single
+    /// words instead. This doesn't affect `word` if it's in the dict.
+    pub fn forbid_hmm_join(&mut self, word: &str) {
+        self.hmm_forbidden.insert(String::from(word));
+    }
+
+    /// Set a weight biasing the routing towards or away from `word`, the default weight is 1.0
     ///
-    /// `sentence`: input text
-    pub fn cut_all<'a>(&self, sentence: &'a str) -> Vec<&'a str> {
-        self.cut_internal(sentence, true, false)
+    /// The log probability of `word` as one step of a route becomes
+    /// `ln(freq) - ln(total) + ln(weight)`, so a weight above 1 makes `word` more likely to be
+    /// picked and a weight below 1 less likely, without changing its frequency. Weights only
+    /// apply to the routing of [`cut`](#method.cut) and the cut modes built on it.
+    ///
+    /// ## Panics
+    ///
+    /// Panics if `weight` is not positive.
+    pub fn set_word_weight(&mut self, word: &str, weight: f64) {
+        assert!(weight > 0.0, "word weight must be positive, got {}", weight);
+        if weight == 1.0 {
+            self.log_weights.remove(word);
+        } else {
+            self.log_weights.insert(String::from(word), weight.ln());
+        }
     }
 
-    /// Cut the input text in search mode
+    /// Always split `word` into single chars when the dict routing picks it
     ///
-    /// ## Params
+    /// Unlike deleting `word`, the dict is left untouched, so longer words containing it are
+    /// still found. The chars aren't joined again by HMM.
+    pub fn add_force_split(&mut self, word: &str) {
+        self.force_split.insert(String::from(word));
+    }
+
+    /// Load dictionary
+    pub fn load_dict<R: BufRead>(&mut self, dict: &mut R) -> Result<(), Error> {
+        let mut buf = String::new();
+        self.total = 0;
+
+        let mut line_no = 0;
+        while dict.read_line(&mut buf)? > 0 {
+            line_no += 1;
+            self.load_dict_line(line_no, &buf)?;
+            buf.clear();
+        }
+        self.total = self.records.iter().map(|n| n.freq).sum();
+
+        Ok(())
+    }
+
+    /// Load dictionary from a string
     ///
-    /// `sentence`: input text
+    /// Same as [load_dict](#method.load_dict) for a dict which is already in memory, without
+    /// going through `std::io`.
+    pub fn load_dict_str(&mut self, dict: &str) -> Result<(), Error> {
+        self.total = 0;
+
+        for (i, line) in dict.lines().enumerate() {
+            self.load_dict_line(i + 1, line)?;
+        }
+        self.total = self.records.iter().map(|n| n.freq).sum();
+
+        Ok(())
+    }
+
+    /// Load a user dict in the format of Python jieba, where words may contain spaces
     ///
-    /// `hmm`: enable HMM or not
-    pub fn cut_for_search<'a>(&self, sentence: &'a str, hmm: bool) -> Vec<&'a str> {
-        let words = self.cut(sentence, hmm);
-        let mut new_words = Vec::with_capacity(words.len());
-        for word in words {
-            let char_indices: Vec<usize> = word.char_indices().map(|x| x.0).collect();
-            let char_count = char_indices.len();
-            if char_count > 2 {
-                for i in 0..char_count - 1 {
-                    let byte_start = char_indices[i];
-                    let gram2 = if i + 2 < char_count {
-                        &word[byte_start..char_indices[i + 2]]
-                    } else {
-                        &word[byte_start..]
-                    };
-                    if self.cedar.exact_match_search(gram2).is_some() {
-                        new_words.push(gram2);
-                    }
+    /// Each line is `word [freq] [tag]`. Fields are only split off the end of the line: the last
+    /// field is the tag if it's made of lowercase ASCII letters, then the last remaining field is
+    /// the frequency if it's made of digits, and the rest of the line is the word, spaces
+    /// included. So "New York ns" adds "New York" tagged "ns", but like in Python jieba
+    /// "hello world" adds "hello" tagged "world"; give such a word a tag to keep it whole. A line
+    /// is never split down to an empty word, "ns" alone adds the word "ns".
+    ///
+    /// Like [add_word](#method.add_word), a missing frequency is given by
+    /// [suggest_freq](#method.suggest_freq) and a missing tag is `""`. The default han regex
+    /// doesn't match spaces, so a word with spaces is only cut as a whole with a
+    /// [han regex](struct.JiebaBuilder.html#method.han_regex) matching them.
+    pub fn load_user_dict<R: BufRead>(&mut self, dict: &mut R) -> Result<(), Error> {
+        let mut buf = String::new();
+        let mut line_no = 0;
+        while dict.read_line(&mut buf)? > 0 {
+            line_no += 1;
+            let mut rest = buf.trim();
+            if rest.is_empty() {
+                buf.clear();
+                continue;
+            }
+
+            let mut tag = None;
+            if let Some((head, last)) = split_last_field(rest) {
+                if last.bytes().all(|b| b.is_ascii_lowercase()) {
+                    tag = Some(last);
+                    rest = head;
                 }
             }
-            if char_count > 3 {
-                for i in 0..char_count - 2 {
-                    let byte_start = char_indices[i];
-                    let gram3 = if i + 3 < char_count {
-                        &word[byte_start..char_indices[i + 3]]
-                    } else {
-                        &word[byte_start..]
-                    };
-                    if self.cedar.exact_match_search(gram3).is_some() {
-                        new_words.push(gram3);
-                    }
+            let mut freq = None;
+            if let Some((head, last)) = split_last_field(rest) {
+                if last.bytes().all(|b| b.is_ascii_digit()) {
+                    let parsed = last.parse::<usize>().map_err(|e| Error::InvalidDictLine {
+                        line: line_no,
+                        message: format!("{}: {}", last, e),
+                    })?;
+                    freq = Some(parsed);
+                    rest = head;
                 }
             }
-            new_words.push(word);
+
+            let word = String::from(rest);
+            let tag = tag.map(String::from);
+            self.add_word(&word, freq, tag.as_deref());
+            buf.clear();
         }
-        new_words
+
+        Ok(())
     }
 
-    /// Tokenize
+    /// Load one line of a dict, `line_no` is the 1-based line number used in errors
+    fn load_dict_line(&mut self, line_no: usize, line: &str) -> Result<(), Error> {
+        let parts: Vec<&str> = line.trim().split_whitespace().collect();
+        if parts.is_empty() {
+            // Skip empty lines
+            return Ok(());
+        }
+
+        let word = parts[0];
+        let freq = parts
+            .get(1)
+            .map(|x| {
+                x.parse::<usize>().map_err(|e| Error::InvalidDictLine {
+                    line: line_no,
+                    message: format!("{}: {}", x, e),
+                })
+            })
+            .unwrap_or(Ok(0))?;
+        let tag = parts.get(2).cloned().unwrap_or("");
+
+        self.insert_record(word, freq, tag);
+        Ok(())
+    }
+
+    /// Merge the dict of `other` into this one
     ///
-    /// ## Params
+    /// This is the same as loading the dict of `other` after the dict of `self`: words only
+    /// known to `other` are added, and words known to both take the frequency from `other`
+    /// while keeping the tag from `self`. The regexes of `self` are kept.
+    pub fn merge(&mut self, other: &Jieba) {
+        for record in &other.records {
+            self.insert_record(&record.word, record.freq, &record.tag);
+        }
+        self.total = self.records.iter().map(|n| n.freq).sum();
+    }
+
+    /// Insert a dict entry without updating `total`, an existing word only gets its frequency updated
+    fn insert_record(&mut self, word: &str, freq: usize, tag: &str) {
+        self.max_freq = std::cmp::max(self.max_freq, freq);
+        let curr_word_len = word.chars().count();
+        if self.longest_word_len < curr_word_len {
+            self.longest_word_len = curr_word_len;
+        }
+
+        match self.cedar.exact_match_search(word) {
+            Some((word_id, _, _)) => {
+                self.records[word_id as usize].freq = freq;
+            }
+            None => {
+                self.records
+                    .push(Record::new(String::from(word), freq, String::from(tag)));
+                let word_id = (self.records.len() - 1) as i32;
+                self.cedar.update(word, word_id);
+                self.update_folded(word, word_id);
+            }
+        };
+    }
+
+    /// Index `word` by its ASCII-lowercased form if it has uppercase ASCII letters
+    fn update_folded(&mut self, word: &str, word_id: i32) {
+        if word.bytes().any(|b| b.is_ascii_uppercase()) {
+            self.cedar_folded.update(&word.to_ascii_lowercase(), word_id);
+        }
+    }
+
+    /// Record index the ASCII-lowercased `word` is indexed under, if `word` has uppercase ASCII letters
+    fn folded_id(&self, word: &str) -> Option<i32> {
+        if word.bytes().any(|b| b.is_ascii_uppercase()) {
+            self.cedar_folded
+                .exact_match_search(&word.to_ascii_lowercase())
+                .map(|(word_id, _, _)| word_id)
+        } else {
+            None
+        }
+    }
+
+    /// Remove the ASCII-lowercased `word` from the index unless another word with the same
+    /// lowercased form took it over
+    fn erase_folded(&mut self, word: &str, word_id: i32) {
+        if self.folded_id(word) == Some(word_id) {
+            self.cedar_folded.erase(&word.to_ascii_lowercase());
+        }
+    }
+
+    /// Look `word` up in the dict, with `ignore_case` an ASCII-lowercased `word` also matches
+    /// the dict words with uppercase ASCII letters
+    #[inline]
+    fn word_id(&self, word: &str, ignore_case: bool) -> Option<usize> {
+        match self.cedar.exact_match_search(word) {
+            Some((word_id, _, _)) => Some(word_id as usize),
+            None if ignore_case => self
+                .cedar_folded
+                .exact_match_search(word)
+                .map(|(word_id, _, _)| word_id as usize),
+            None => None,
+        }
+    }
+
+    /// Check whether `word` is a word in the dict
     ///
-    /// `sentence`: input text
+    /// The whole input has to match a dict entry, a word merely starting with a dict
+    /// entry isn't considered contained.
+    pub fn contains_word(&self, word: &str) -> bool {
+        self.cedar.exact_match_search(word).is_some()
+    }
+
+    /// Get the words in the dict starting with `prefix`, sorted
     ///
-    /// `mode`: tokenize mode
+    /// `prefix` itself is included if it's a word in the dict. The lookup walks the dict trie,
+    /// so it doesn't scan the whole dict.
+    pub fn words_with_prefix(&self, prefix: &str) -> Vec<&str> {
+        let mut words: Vec<&str> = if prefix.is_empty() {
+            self.records.iter().map(|record| record.word.as_str()).collect()
+        } else {
+            self.cedar
+                .common_prefix_predict_iter(prefix)
+                .map(|(word_id, _)| self.records[word_id as usize].word.as_str())
+                .collect()
+        };
+        words.sort_unstable();
+        words
+    }
+
+    /// Get the tag of `word` in the dict
     ///
-    /// `hmm`: enable HMM or not
-    pub fn tokenize<'a>(&self, sentence: &'a str, mode: TokenizeMode, hmm: bool) -> Vec<Token<'a>> {
-        let words = self.cut(sentence, hmm);
-        let mut tokens = Vec::with_capacity(words.len());
-        let mut start = 0;
-        match mode {
-            TokenizeMode::Default => {
-                for word in words {
-                    let width = word.chars().count();
-                    tokens.push(Token {
-                        word,
-                        start,
-                        end: start + width,
-                    });
-                    start += width;
-                }
+    /// Returns `None` if `word` isn't in the dict or has no tag.
+    pub fn word_tag(&self, word: &str) -> Option<&str> {
+        let (word_id, _, _) = self.cedar.exact_match_search(word)?;
+        let tag = &self.records[word_id as usize].tag;
+        if tag.is_empty() {
+            None
+        } else {
+            Some(tag)
+        }
+    }
+
+    /// Iterate over all the words in the dict as `(word, freq, tag)`
+    ///
+    /// Words are yielded in insertion order, that is in the order they were loaded from the
+    /// dict files followed by the words added with [add_word](#method.add_word). Updating an
+    /// existing word changes its frequency but not its position, and deleting a word with
+    /// [del_word](#method.del_word) moves the last word to its position.
+    pub fn words(&self) -> impl Iterator<Item = (&str, usize, &str)> + '_ {
+        self.records
+            .iter()
+            .map(|record| (record.word.as_str(), record.freq, record.tag.as_str()))
+    }
+
+    /// Write the dict in the `word freq tag` format read by [load_dict](#method.load_dict)
+    ///
+    /// Words are written one per line in the order of [words](#method.words), the tag is
+    /// omitted when it's empty. Loading the output into an empty instance gives back the same
+    /// dict, except for words with whitespace, like the ones loaded by
+    /// [load_user_dict](#method.load_user_dict), which can't be read back. Every line is written
+    /// on its own, wrap `writer` in a `BufWriter` when writing to a file.
+    pub fn write_dict<W: Write>(&self, mut writer: W) -> io::Result<()> {
+        for record in &self.records {
+            if record.tag.is_empty() {
+                writeln!(writer, "{} {}", record.word, record.freq)?;
+            } else {
+                writeln!(writer, "{} {} {}", record.word, record.freq, record.tag)?;
             }
-            TokenizeMode::Search => {
-                for word in words {
-                    let width = word.chars().count();
-                    if width > 2 {
-                        let char_indices: Vec<usize> = word.char_indices().map(|x| x.0).collect();
-                        for i in 0..width - 1 {
-                            let byte_start = char_indices[i];
-                            let gram2 = if i + 2 < width {
-                                &word[byte_start..char_indices[i + 2]]
-                            } else {
-                                &word[byte_start..]
-                            };
-                            if self.cedar.exact_match_search(gram2).is_some() {
-                                tokens.push(Token {
-                                    word: gram2,
-                                    start: start + i,
-                                    end: start + i + 2,
-                                });
-                            }
-                        }
-                        if width > 3 {
-                            for i in 0..width - 2 {
-                                let byte_start = char_indices[i];
-                                let gram3 = if i + 3 < width {
-                                    &word[byte_start..char_indices[i + 3]]
-                                } else {
-                                    &word[byte_start..]
-                                };
-                                if self.cedar.exact_match_search(gram3).is_some() {
-                                    tokens.push(Token {
-                                        word: gram3,
-                                        start: start + i,
-                                        end: start + i + 3,
-                                    });
-                                }
-                            }
+        }
+        writer.flush()
+    }
+
+    fn get_word_freq(&self, word: &str, default: usize) -> usize {
+        match self.cedar.exact_match_search(word) {
+            Some((word_id, _, _)) => self.records[word_id as usize].freq,
+            _ => default,
+        }
+    }
+
+    /// `ln(total)` of the dict, `ln(0)` is `-inf` which would turn the route scores into NaN
+    /// for an empty dict, hence it's treated as 0.
+    #[inline]
+    fn log_total(&self) -> f64 {
+        if self.total == 0 {
+            0.0
+        } else {
+            (self.total as f64).ln()
+        }
+    }
+
+    /// Log probability of `word` as one step of a route, `ln(freq) - ln(total)`
+    ///
+    /// Words which aren't in the dict count with the fallback frequency, words with a zero
+    /// frequency with a frequency of 1.
+    #[inline]
+    fn word_log_prob(&self, word: &str) -> f64 {
+        let freq = std::cmp::max(self.get_word_freq(word, self.fallback_freq), 1);
+        (freq as f64).ln() - self.log_total()
+    }
+
+    /// Route score of `word` taken as a whole, its log probability adjusted by its weight
+    ///
+    /// A dict word with a zero frequency is never picked by the routing and scores `-inf`.
+    fn token_score(&self, word: &str) -> f64 {
+        if self.get_word_freq(word, self.fallback_freq) == 0 {
+            return f64::NEG_INFINITY;
+        }
+        self.word_log_prob(word) + self.log_weights.get(word).copied().unwrap_or(0.0)
+    }
+
+    /// Suggest word frequency to force the characters in a word to be joined or splitted.
+    pub fn suggest_freq(&self, segment: &str) -> usize {
+        let logtotal = self.log_total();
+        let logfreq = self.cut(segment, false).iter().fold(0f64, |freq, word| {
+            freq + (self.get_word_freq(word, 1) as f64).ln() - logtotal
+        });
+        std::cmp::max((logfreq + logtotal).exp() as usize + 1, self.get_word_freq(segment, 1))
+    }
+
+    /// Check whether routing can ever pick `word` as a whole
+    ///
+    /// `word` is cut on its own without HMM and without merging ASCII runs, it's reachable if
+    /// it's in the dict and comes out as a single word. A word which isn't reachable on its own
+    /// won't be picked in any context either; [`suggest_freq`](#method.suggest_freq) gives a
+    /// frequency high enough to make it reachable.
+    pub fn check_word_reachable(&self, word: &str) -> bool {
+        if !self.contains_word(word) {
+            return false;
+        }
+        let options = CutOptions::default().merge_ascii(false);
+        self.cut_with_options(word, &options) == [word]
+    }
+
+    #[allow(clippy::ptr_arg)]
+    /// Compute the most likely route through `dag`, a word crossing one of the hints of `params`
+    /// is penalized like an extra word of frequency 1
+    fn calc(
+        &self,
+        sentence: &str,
+        dag: &StaticSparseDAG,
+        route: &mut Vec<RouteStep>,
+        ignore_case: bool,
+        params: RouteParams,
+    ) {
+        let str_len = sentence.len();
+
+        if str_len + 1 > route.len() {
+            route.resize(str_len + 1, (0.0, 0, 0));
+        }
+
+        let logtotal = self.log_total();
+        let score = |word: &str, freq: usize| match params.scorer {
+            Some(scorer) => scorer.score(word, freq, self.total),
+            None => (freq as f64).ln() - logtotal,
+        };
+        let mut prev_byte_start = str_len;
+        let curr = sentence.char_indices().map(|x| x.0).rev();
+        for byte_start in curr {
+            let pair = dag
+                .iter_edges(byte_start)
+                .filter_map(|byte_end| {
+                    let wfrag = if byte_end == str_len {
+                        &sentence[byte_start..]
+                    } else {
+                        &sentence[byte_start..byte_end]
+                    };
+
+                    if let Some(allowed) = params.allowed {
+                        if wfrag.chars().nth(1).is_some() && !allowed(wfrag) {
+                            return None;
                         }
                     }
-                    tokens.push(Token {
-                        word,
-                        start,
-                        end: start + width,
-                    });
-                    start += width;
+
+                    let dict_freq = self
+                        .word_id(wfrag, ignore_case)
+                        .map(|word_id| self.records[word_id].freq);
+                    let freq = dict_freq.unwrap_or(self.fallback_freq);
+
+                    // Words with a zero frequency can never be chosen, skipping them also
+                    // avoids `ln(0)` turning the route into `-inf`
+                    if freq == 0 {
+                        return None;
+                    }
+
+                    let log_weight = if self.log_weights.is_empty() {
+                        0.0
+                    } else {
+                        self.log_weights.get(wfrag).copied().unwrap_or(0.0)
+                    };
+
+                    let hint_penalty = if crosses_hint(params.hints, byte_start, byte_end) {
+                        logtotal
+                    } else {
+                        0.0
+                    };
+
+                    Some((
+                        score(wfrag, freq) + log_weight - hint_penalty + route[byte_end].0,
+                        byte_end,
+                        dict_freq.unwrap_or(0),
+                    ))
+                })
+                .max_by(|x, y| cmp_route(&(x.0, x.1), &(y.0, y.1)));
+
+            if let Some(p) = pair {
+                route[byte_start] = p;
+            } else {
+                let byte_end = prev_byte_start;
+                let word = &sentence[byte_start..byte_end];
+                route[byte_start] = (score(word, self.fallback_freq) + route[byte_end].0, byte_end, 0);
+            }
+
+            prev_byte_start = byte_start;
+        }
+    }
+
+    /// Build the DAG of the dict words in `sentence`, with `max_chars` only the words of at most
+    /// that many chars, without `ascii_dict` only the words without ASCII letters or digits
+    fn dag(
+        &self,
+        sentence: &str,
+        dag: &mut StaticSparseDAG,
+        ignore_case: bool,
+        max_chars: Option<usize>,
+        ascii_dict: bool,
+    ) {
+        let mut iter = sentence.char_indices().peekable();
+        let mut ends = Vec::new();
+        while let Some((byte_start, _)) = iter.next() {
+            dag.start(byte_start);
+            let haystack = &sentence[byte_start..];
+            // Prefixes come shortest first, so matching stops at the first one past the bound
+            let max_end_index = match max_chars {
+                Some(max_chars) => haystack
+                    .char_indices()
+                    .nth(std::cmp::max(max_chars, 1))
+                    .map_or(haystack.len(), |(byte_index, _)| byte_index),
+                None => haystack.len(),
+            };
+
+            if ignore_case {
+                // A folded word can have the same key as a lowercase word, keep each end once
+                ends.clear();
+                ends.extend(
+                    self.cedar
+                        .common_prefix_iter(haystack)
+                        .map(|(_, end_index)| end_index)
+                        .take_while(|&end_index| end_index < max_end_index),
+                );
+                ends.extend(
+                    self.cedar_folded
+                        .common_prefix_iter(haystack)
+                        .map(|(_, end_index)| end_index)
+                        .take_while(|&end_index| end_index < max_end_index),
+                );
+                ends.sort_unstable();
+                ends.dedup();
+                ends.retain(|&end_index| ascii_dict || !has_ascii_alphanumeric(&haystack[..=end_index]));
+                for end_index in &ends {
+                    dag.insert(end_index + byte_start + 1);
                 }
+            } else {
+                for (_, end_index) in self.cedar.common_prefix_iter(haystack) {
+                    if end_index >= max_end_index {
+                        break;
+                    }
+                    if !ascii_dict && has_ascii_alphanumeric(&haystack[..=end_index]) {
+                        continue;
+                    }
+                    dag.insert(end_index + byte_start + 1);
+                }
+            }
+
+            dag.commit();
+        }
+    }
+
+    fn cut_all_internal<'a>(&self, sentence: &'a str, words: &mut Vec<&'a str>, ignore_case: bool) {
+        let str_len = sentence.len();
+        let mut dag = StaticSparseDAG::with_size_hint(sentence.len());
+        self.dag(sentence, &mut dag, ignore_case, None, true);
+
+        let curr = sentence.char_indices().map(|x| x.0);
+        for byte_start in curr {
+            for byte_end in dag.iter_edges(byte_start) {
+                let word = if byte_end == str_len {
+                    &sentence[byte_start..]
+                } else {
+                    &sentence[byte_start..byte_end]
+                };
+
+                words.push(word)
+            }
+        }
+    }
+
+    /// Push the run of single ASCII chars `sentence[byte_start..byte_end]`, merged into one word
+    /// if it has any alphanumeric
+    fn push_ascii_run<'a, W: WordSink<'a>>(
+        sentence: &'a str,
+        byte_start: usize,
+        byte_end: usize,
+        route: &[RouteStep],
+        words: &mut W,
+    ) {
+        let run = &sentence[byte_start..byte_end];
+        if run.bytes().any(|b| b.is_ascii_alphanumeric()) {
+            let freq = if run.len() == 1 { route[byte_start].2 } else { 0 };
+            let source = if freq > 0 {
+                TokenSource::Dictionary
+            } else {
+                TokenSource::Ascii
+            };
+            words.push_word(run, source, freq);
+        } else {
+            for x in byte_start..byte_end {
+                push_routed_char(sentence, x, route, words);
             }
         }
-        tokens
     }
 
-    /// Tag the input text
-    ///
-    /// ## Params
-    ///
-    /// `sentence`: input text
-    ///
-    /// `hmm`: enable HMM or not
-    pub fn tag<'a>(&'a self, sentence: &'a str, hmm: bool) -> Vec<Tag> {
-        let words = self.cut(sentence, hmm);
-        words
-            .into_iter()
-            .map(|word| {
-                if let Some((word_id, _, _)) = self.cedar.exact_match_search(word) {
-                    let t = &self.records[word_id as usize].tag;
-                    return Tag { word, tag: t };
-                }
-                let mut eng = 0;
-                let mut m = 0;
-                for chr in word.chars() {
-                    if chr.is_ascii_alphanumeric() {
-                        eng += 1;
-                        if chr.is_ascii_digit() {
-                            m += 1;
-                        }
-                    }
-                }
-                let tag = if eng == 0 {
-                    "x"
-                } else if eng == m {
-                    "m"
-                } else {
-                    "eng"
-                };
-                Tag { word, tag }
-            })
-            .collect()
+    fn cut_dag_no_hmm<'a, W: WordSink<'a>>(
+        &self,
+        sentence: &'a str,
+        words: &mut W,
+        buffers: &mut RouteBuffers<'a>,
+        options: &CutOptions,
+        params: RouteParams,
+    ) {
+        let RouteBuffers { route, dag, .. } = buffers;
+        self.dag(
+            sentence,
+            dag,
+            options.ignore_ascii_case,
+            options.max_match_chars,
+            options.ascii_dict,
+        );
+        self.calc(sentence, dag, route, options.ignore_ascii_case, params);
+        let mut x = 0;
+        let mut left: Option<usize> = None;
+
+        while x < sentence.len() {
+            let y = route[x].1;
+            let l_str = if y < sentence.len() {
+                &sentence[x..y]
+            } else {
+                &sentence[x..]
+            };
+
+            if options.merge_ascii && l_str.len() == 1 && l_str.bytes().all(is_ascii_word_byte) {
+                if let Some(byte_start) = left.filter(|_| params.hints.binary_search(&x).is_ok()) {
+                    Self::push_ascii_run(sentence, byte_start, x, route, words);
+                    left = None;
+                }
+                if left.is_none() {
+                    left = Some(x);
+                }
+            } else {
+                if let Some(byte_start) = left {
+                    Self::push_ascii_run(sentence, byte_start, x, route, words);
+                    left = None;
+                }
+
+                let word = if y < sentence.len() {
+                    &sentence[x..y]
+                } else {
+                    &sentence[x..]
+                };
+
+                self.push_routed(word, route[x].2, words);
+            }
+            x = y;
+        }
+
+        if let Some(byte_start) = left {
+            Self::push_ascii_run(sentence, byte_start, sentence.len(), route, words);
+        }
+
+        dag.clear();
+        route.clear();
+    }
+
+    /// Push a word picked by the dict routing with its dict frequency `freq`, split into chars if
+    /// it's in `force_split`
+    #[inline]
+    fn push_routed<'a, W: WordSink<'a>>(&self, word: &'a str, freq: usize, words: &mut W) {
+        if !self.force_split.is_empty() && self.force_split.contains(word) {
+            for (i, ch) in word.char_indices() {
+                let ch = &word[i..i + ch.len_utf8()];
+                let freq = self.get_word_freq(ch, 0);
+                words.push_word(ch, routed_source(freq), freq);
+            }
+        } else {
+            words.push_word(word, routed_source(freq), freq);
+        }
+    }
+
+    /// Cut `word`, a run of single chars of the han block `sentence`, with HMM, splitting the
+    /// words in `hmm_forbidden` back into chars
+    fn cut_hmm<'a, W: WordSink<'a>>(
+        &self,
+        sentence: &'a str,
+        word: &'a str,
+        words: &mut W,
+        buffers: &mut RouteBuffers<'a>,
+    ) {
+        let RouteBuffers {
+            route,
+            v,
+            prev,
+            path,
+            hmm_words,
+            ..
+        } = buffers;
+        hmm::cut_with_allocated_memory(word, hmm_words, v, prev, path);
+
+        for hmm_word in hmm_words.drain(..) {
+            let byte_start = hmm_word.as_ptr() as usize - sentence.as_ptr() as usize;
+            if hmm_word.chars().nth(1).is_none() {
+                push_routed_char(sentence, byte_start, route, words);
+            } else if !self.hmm_forbidden.is_empty() && self.hmm_forbidden.contains(hmm_word) {
+                for (i, _) in hmm_word.char_indices() {
+                    push_routed_char(sentence, byte_start + i, route, words);
+                }
+            } else if hmm_word.is_ascii() {
+                // HMM only joins han chars, the other runs are split off by its own regex
+                words.push_word(hmm_word, TokenSource::Ascii, 0);
+            } else {
+                words.push_word(hmm_word, TokenSource::Hmm, 0);
+            }
+        }
+    }
+
+    fn cut_dag_hmm<'a, W: WordSink<'a>>(
+        &self,
+        sentence: &'a str,
+        words: &mut W,
+        buffers: &mut RouteBuffers<'a>,
+        options: &CutOptions,
+        params: RouteParams,
+    ) {
+        let ignore_case = options.ignore_ascii_case;
+        self.dag(
+            sentence,
+            &mut buffers.dag,
+            ignore_case,
+            options.max_match_chars,
+            options.ascii_dict,
+        );
+        self.calc(sentence, &buffers.dag, &mut buffers.route, ignore_case, params);
+        let mut x = 0;
+        let mut left: Option<usize> = None;
+
+        while x < sentence.len() {
+            let y = buffers.route[x].1;
+
+            if sentence[x..y].chars().count() == 1 {
+                // HMM must not join chars across a hinted boundary either
+                if let Some(byte_start) = left.filter(|_| params.hints.binary_search(&x).is_ok()) {
+                    self.cut_single_chars(sentence, byte_start, x, words, buffers, ignore_case);
+                    left = None;
+                }
+                if left.is_none() {
+                    left = Some(x);
+                }
+            } else {
+                if let Some(byte_start) = left {
+                    self.cut_single_chars(sentence, byte_start, x, words, buffers, ignore_case);
+                    left = None;
+                }
+                let word = if y < sentence.len() {
+                    &sentence[x..y]
+                } else {
+                    &sentence[x..]
+                };
+                self.push_routed(word, buffers.route[x].2, words);
+            }
+            x = y;
+        }
+
+        if let Some(byte_start) = left {
+            self.cut_single_chars(sentence, byte_start, sentence.len(), words, buffers, ignore_case);
+        }
+
+        buffers.dag.clear();
+        buffers.route.clear();
+    }
+
+    /// Cut `sentence[byte_start..byte_end]`, a run of chars routing left as single chars, with
+    /// HMM unless the run is a dict word
+    fn cut_single_chars<'a, W: WordSink<'a>>(
+        &self,
+        sentence: &'a str,
+        byte_start: usize,
+        byte_end: usize,
+        words: &mut W,
+        buffers: &mut RouteBuffers<'a>,
+        ignore_case: bool,
+    ) {
+        let word = &sentence[byte_start..byte_end];
+        if word.chars().count() == 1 {
+            push_routed_char(sentence, byte_start, &buffers.route, words);
+        } else if self.word_id(word, ignore_case).is_none() {
+            self.cut_hmm(sentence, word, words, buffers);
+        } else {
+            for (i, _) in word.char_indices() {
+                push_routed_char(sentence, byte_start + i, &buffers.route, words);
+            }
+        }
+    }
+
+    /// Whether `sentence` is a dict word which routing would keep whole
+    ///
+    /// Any other route starts with a shorter word and is followed by at least one more word, whose
+    /// log probabilities are at most `ln(max_freq) - ln(total)`, `max_freq` also bounding the
+    /// fallback frequency of the chars which aren't dict words. So a word which is at least as
+    /// likely as its most likely prefix followed by such a word, or as such a word followed by its
+    /// most likely suffix, beats every other route.
+    fn is_single_word(&self, sentence: &str) -> bool {
+        let freq = match self.cedar.exact_match_search(sentence) {
+            Some((word_id, _, _)) => self.records[word_id as usize].freq,
+            None => return false,
+        };
+        let max_freq = std::cmp::max(self.max_freq, self.fallback_freq);
+        if freq == 0 || max_freq > self.total || self.force_split.contains(sentence) || !self.log_weights.is_empty() {
+            return false;
+        }
+        match self.re_han.find(sentence) {
+            Some(m) if m.start() == 0 && m.end() == sentence.len() => {}
+            _ => return false,
+        }
+        let first_char_len = match sentence.chars().next() {
+            Some(ch) if ch.len_utf8() < sentence.len() => ch.len_utf8(),
+            _ => return true,
+        };
+        // Routing skips the single chars which are dict words with a zero frequency and falls
+        // back to the fallback frequency for them, which the bound below doesn't account for
+        let last_char_start = sentence.char_indices().last().map_or(0, |(byte_start, _)| byte_start);
+        for single_char in &[&sentence[..first_char_len], &sentence[last_char_start..]] {
+            if self.get_word_freq(single_char, self.fallback_freq) == 0 {
+                return false;
+            }
+        }
+
+        let best_prefix = self
+            .cedar
+            .common_prefix_iter(sentence)
+            .filter(|&(word_id, end_index)| end_index + 1 < sentence.len() && self.records[word_id as usize].freq > 0)
+            .map(|(_, end_index)| self.word_log_prob(&sentence[..=end_index]))
+            .chain(std::iter::once(self.word_log_prob(&sentence[..first_char_len])))
+            .fold(f64::NEG_INFINITY, f64::max);
+        let best_suffix = sentence
+            .char_indices()
+            .skip(1)
+            .map(|(byte_start, _)| &sentence[byte_start..])
+            .filter(|suffix| suffix.chars().nth(1).is_none() || self.get_word_freq(suffix, 0) > 0)
+            .map(|suffix| self.word_log_prob(suffix))
+            .fold(f64::NEG_INFINITY, f64::max);
+
+        let max_log_prob = (max_freq as f64).ln() - self.log_total();
+        (freq as f64).ln() - self.log_total() >= max_log_prob + best_prefix.min(best_suffix)
+    }
+
+    fn cut_internal<'a>(&self, sentence: &'a str, options: &CutOptions) -> Vec<&'a str> {
+        if sentence.is_empty() {
+            return Vec::new();
+        }
+        // Short queries are often a single common word, which doesn't need routing
+        let routes_all_words = options.ascii_dict && options.max_match_chars.is_none();
+        if !options.cut_all && routes_all_words && self.is_single_word(sentence) {
+            return vec![sentence];
+        }
+
+        let heuristic_capacity = sentence.len() / 2;
+        // The HMM buffers start empty, they're grown to the longest fragment HMM actually runs on
+        let mut ctx = CutContext::with_capacity(heuristic_capacity);
+        self.cut_with_context(&mut ctx, sentence, options);
+        ctx.words
+    }
+
+    fn cut_with_context<'a>(&self, ctx: &mut CutContext<'a>, sentence: &'a str, options: &CutOptions) {
+        let CutContext { words, buffers } = ctx;
+        if options.cut_all {
+            self.cut_all_blocks(sentence, words, options.ignore_ascii_case);
+        } else {
+            self.cut_blocks(sentence, options, words, buffers);
+        }
+    }
+
+    /// Cut `sentence` in full mode, see [`cut_all`](#method.cut_all)
+    fn cut_all_blocks<'a>(&self, sentence: &'a str, words: &mut Vec<&'a str>, ignore_case: bool) {
+        for state in SplitMatches::new(&self.re_han_cut_all, sentence) {
+            match state {
+                SplitState::Matched(_) => {
+                    let block = state.into_str();
+                    assert!(!block.is_empty());
+                    self.cut_all_internal(block, words, ignore_case);
+                }
+                SplitState::Unmatched(_) => {
+                    let block = state.into_str();
+                    assert!(!block.is_empty());
+                    let skip_splitter = SplitMatches::new(&self.re_skip_cut_all, block);
+                    words.extend(skip_splitter.map(SplitState::into_str).filter(|word| !word.is_empty()));
+                }
+            }
+        }
+    }
+
+    /// Cut `sentence` by routing its han blocks through the dict, passing every word to `words`
+    /// along with where it comes from
+    fn cut_blocks<'a, W: WordSink<'a>>(
+        &self,
+        sentence: &'a str,
+        options: &CutOptions,
+        words: &mut W,
+        buffers: &mut RouteBuffers<'a>,
+    ) {
+        for state in SplitMatches::new(&self.re_han, sentence) {
+            match state {
+                SplitState::Matched(_) => {
+                    let block = state.into_str();
+                    assert!(!block.is_empty());
+
+                    if options.hmm {
+                        self.cut_dag_hmm(block, words, buffers, options, RouteParams::default());
+                    } else {
+                        self.cut_dag_no_hmm(block, words, buffers, options, RouteParams::default());
+                    }
+                }
+                SplitState::Unmatched(_) => {
+                    let block = state.into_str();
+                    assert!(!block.is_empty());
+
+                    let skip_splitter = SplitMatches::new(&self.re_skip, block);
+                    for skip_state in skip_splitter {
+                        let word = skip_state.into_str();
+                        if word.is_empty() {
+                            continue;
+                        }
+                        if self.re_skip.is_match(word) {
+                            words.push_word(word, TokenSource::Skip, 0);
+                        } else {
+                            push_fullwidth_runs(word, words);
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    /// Cut the input text
+    ///
+    /// Empty text is cut into no words, and whitespaces are kept as words of one char, see
+    /// [`CutOptions::keep_whitespace`](struct.CutOptions.html#method.keep_whitespace).
+    ///
+    /// ## Params
+    ///
+    /// `sentence`: input text
+    ///
+    /// `hmm`: enable HMM or not
+    pub fn cut<'a>(&self, sentence: &'a str, hmm: bool) -> Vec<&'a str> {
+        self.cut_with_options(sentence, &CutOptions::default().hmm(hmm))
+    }
+
+    /// Cut the input text with options
+    ///
+    /// ## Params
+    ///
+    /// `sentence`: input text
+    ///
+    /// `options`: cut options, see [CutOptions](struct.CutOptions.html)
+    pub fn cut_with_options<'a>(&self, sentence: &'a str, options: &CutOptions) -> Vec<&'a str> {
+        let mut words = if options.lowercase_ascii || options.ignore_ascii_case {
+            // Lowercasing ASCII letters doesn't move any byte, so the words cut from the folded
+            // text can be mapped back to the same byte ranges of the original text.
+            let folded = sentence.to_ascii_lowercase();
+            self.cut_by_mode(&folded, options)
+                .into_iter()
+                .map(|word| {
+                    let byte_start = word.as_ptr() as usize - folded.as_ptr() as usize;
+                    &sentence[byte_start..byte_start + word.len()]
+                })
+                .collect()
+        } else {
+            self.cut_by_mode(sentence, options)
+        };
+
+        if options.collapse_punctuation && !options.search && !options.cut_all {
+            words = collapse_punctuation(sentence, words);
+        }
+        if options.merge_single_chars != MergeSingleChars::None && !options.search && !options.cut_all {
+            words = self.merge_single_chars(sentence, words, options.merge_single_chars);
+        }
+        if options.attach_punctuation && !options.search && !options.cut_all {
+            words = attach_punctuation(sentence, words);
+        }
+        if !options.keep_whitespace {
+            words.retain(|word| !word.chars().all(char::is_whitespace));
+        }
+        words
+    }
+
+    /// Merge the runs of single chars of `kind` which aren't dict words, `words` being
+    /// consecutive slices of `sentence`
+    fn merge_single_chars<'a>(&self, sentence: &'a str, words: Vec<&'a str>, kind: MergeSingleChars) -> Vec<&'a str> {
+        let merge_cjk = matches!(kind, MergeSingleChars::Cjk | MergeSingleChars::Both);
+        let merge_ascii = matches!(kind, MergeSingleChars::Ascii | MergeSingleChars::Both);
+        // Class of the single chars to merge, `Some(true)` for CJK and `Some(false)` for ASCII
+        let class = |word: &str| {
+            let mut chars = word.chars();
+            let ch = match (chars.next(), chars.next()) {
+                (Some(ch), None) => ch,
+                _ => return None,
+            };
+            let class = if merge_cjk && RE_HAN_CUT_ALL.is_match(word) {
+                true
+            } else if merge_ascii && ch.is_ascii_alphanumeric() {
+                false
+            } else {
+                return None;
+            };
+            if self.contains_word(word) {
+                None
+            } else {
+                Some(class)
+            }
+        };
+        merge_runs(sentence, words, class)
+            .into_iter()
+            .map(|(word, _)| word)
+            .collect()
+    }
+
+    fn cut_by_mode<'a>(&self, sentence: &'a str, options: &CutOptions) -> Vec<&'a str> {
+        let words = if !options.keep_numbers || options.cut_all {
+            self.cut_words_by_mode(sentence, options)
+        } else {
+            self.cut_numbers_by_mode(sentence, options)
+        };
+
+        match options.max_token_chars {
+            Some(max_chars) if words.iter().any(|word| word.chars().count() > max_chars) => {
+                let mut bounded = Vec::with_capacity(words.len());
+                for word in words {
+                    if word.chars().count() > max_chars {
+                        self.cut_bounded(word, max_chars, &mut bounded);
+                    } else {
+                        bounded.push(word);
+                    }
+                }
+                bounded
+            }
+            _ => words,
+        }
+    }
+
+    /// Cut `word` along the most likely route made of words of at most `max_chars` chars
+    fn cut_bounded<'a>(&self, word: &'a str, max_chars: usize, words: &mut Vec<&'a str>) {
+        let max_chars = std::cmp::max(max_chars, 1);
+        let bounds: Vec<usize> = word
+            .char_indices()
+            .map(|x| x.0)
+            .chain(std::iter::once(word.len()))
+            .collect();
+
+        // `route[i]` is the best `(log prob, char end)` from char `i` to the end of the word
+        let mut route = vec![(0.0, 0); bounds.len()];
+        for start in (0..bounds.len() - 1).rev() {
+            route[start] = (start + 1..=std::cmp::min(start + max_chars, bounds.len() - 1))
+                .filter_map(|end| {
+                    let freq = self.get_word_freq(&word[bounds[start]..bounds[end]], 0);
+                    if freq > 0 || end == start + 1 {
+                        Some((
+                            self.word_log_prob(&word[bounds[start]..bounds[end]]) + route[end].0,
+                            end,
+                        ))
+                    } else {
+                        None
+                    }
+                })
+                .max_by(cmp_route)
+                .unwrap();
+        }
+
+        let mut start = 0;
+        while start < bounds.len() - 1 {
+            let end = route[start].1;
+            words.push(&word[bounds[start]..bounds[end]]);
+            start = end;
+        }
+    }
+
+    fn cut_numbers_by_mode<'a>(&self, sentence: &'a str, options: &CutOptions) -> Vec<&'a str> {
+        // Numbers are cut out first, only the text between them goes through the dict
+        let is_ascii_word_char = |ch: Option<char>| matches!(ch, Some(ch) if ch.is_ascii_alphanumeric() || ch == '.');
+        let mut words = Vec::new();
+        let mut pos = 0;
+        for number in RE_NUMBER.find_iter(sentence) {
+            if is_ascii_word_char(sentence[..number.start()].chars().next_back())
+                || is_ascii_word_char(sentence[number.end()..].chars().next())
+            {
+                continue;
+            }
+            words.extend(self.cut_words_by_mode(&sentence[pos..number.start()], options));
+            words.push(number.as_str());
+            pos = number.end();
+        }
+        words.extend(self.cut_words_by_mode(&sentence[pos..], options));
+        words
+    }
+
+    fn cut_words_by_mode<'a>(&self, sentence: &'a str, options: &CutOptions) -> Vec<&'a str> {
+        if options.cut_all {
+            self.cut_internal(sentence, options)
+        } else if options.search {
+            let words = self.cut_internal(sentence, options);
+            let mut new_words = Vec::with_capacity(words.len());
+            let mut grams = Vec::new();
+            for word in words {
+                self.search_grams(word, &mut grams);
+                let has_grams = !grams.is_empty();
+                new_words.append(&mut grams);
+                if options.search_full_word || !has_grams {
+                    new_words.push(word);
+                }
+            }
+            new_words
+        } else {
+            self.cut_internal(sentence, options)
+        }
+    }
+
+    /// Cut the input text, return the words with where they come from
+    ///
+    /// The words are the ones [`cut`](#method.cut) returns, each labelled by the step of the
+    /// cut which produced it, so a word joined by HMM is `Hmm` even if it's also in the dict.
+    ///
+    /// ## Params
+    ///
+    /// `sentence`: input text
+    ///
+    /// `hmm`: enable HMM or not
+    pub fn cut_with_source<'a>(&self, sentence: &'a str, hmm: bool) -> Vec<(&'a str, TokenSource)> {
+        let mut words = Vec::new();
+        let mut buffers = RouteBuffers::with_capacity(sentence.len());
+        self.cut_blocks(sentence, &CutOptions::default().hmm(hmm), &mut words, &mut buffers);
+        words
+    }
+
+    /// Whether `token` is a separator rather than a word, like punctuation or whitespace
+    ///
+    /// Separators are the tokens cut from the text outside of the blocks matched by the han
+    /// regex, see [`JiebaBuilder::han_regex`](struct.JiebaBuilder.html#method.han_regex), except
+    /// for the runs of fullwidth letters and digits like "ＳＫ－ＩＩ", which are kept whole as
+    /// words. They're never routed through the dict, [`stats`](#method.stats) and
+    /// [`token_similarity`](#method.token_similarity) don't count them as words.
+    ///
+    /// ## Params
+    ///
+    /// `token`: a token returned by cutting
+    pub fn is_separator(&self, token: &str) -> bool {
+        !token.is_empty() && !self.re_han.is_match(token) && !is_fullwidth_word(token)
+    }
+
+    /// Cut the input text, return the words with their frequency in the dict
+    ///
+    /// The frequencies are the ones routing looked up, so the words it didn't take from the dict,
+    /// like the ones recovered by HMM, the merged ASCII runs and the separators, have a frequency
+    /// of 0.
+    ///
+    /// ## Params
+    ///
+    /// `sentence`: input text
+    ///
+    /// `hmm`: enable HMM or not
+    pub fn cut_with_freq<'a>(&self, sentence: &'a str, hmm: bool) -> Vec<(&'a str, usize)> {
+        let mut words = Vec::new();
+        let mut buffers = RouteBuffers::with_capacity(sentence.len());
+        self.cut_blocks(sentence, &CutOptions::default().hmm(hmm), &mut words, &mut buffers);
+        words
+    }
+
+    /// Cut the input text, return the words which aren't in the dict
+    ///
+    /// These are the words recovered by HMM, the chars which didn't match any dict word and the
+    /// runs of ASCII letters and digits. Whitespace and punctuation, which are never looked up in
+    /// the dict, aren't returned.
+    ///
+    /// ## Params
+    ///
+    /// `sentence`: input text
+    ///
+    /// `hmm`: enable HMM or not
+    pub fn oov_words<'a>(&self, sentence: &'a str, hmm: bool) -> Vec<&'a str> {
+        self.cut(sentence, hmm)
+            .into_iter()
+            .filter(|word| !self.contains_word(word) && !self.is_separator(word))
+            .collect()
+    }
+
+    /// Cut the input text once and compute statistics of its words
+    ///
+    /// Whitespace and punctuation aren't counted as words, the out of vocabulary words are the
+    /// ones returned by [`oov_words`](#method.oov_words).
+    ///
+    /// ## Params
+    ///
+    /// `sentence`: input text
+    ///
+    /// `hmm`: enable HMM or not
+    pub fn stats(&self, sentence: &str, hmm: bool) -> TextStats {
+        let words: Vec<&str> = self
+            .cut(sentence, hmm)
+            .into_iter()
+            .filter(|word| !self.is_separator(word))
+            .collect();
+        if words.is_empty() {
+            return TextStats::default();
+        }
+
+        let distinct: HashSet<&str> = words.iter().copied().collect();
+        let chars: usize = words.iter().map(|word| word.chars().count()).sum();
+        let oov = words.iter().filter(|word| !self.contains_word(word)).count();
+        TextStats {
+            tokens: words.len(),
+            distinct_words: distinct.len(),
+            average_word_len: chars as f64 / words.len() as f64,
+            oov_ratio: oov as f64 / words.len() as f64,
+        }
+    }
+
+    /// Jaccard similarity of the sets of words the two texts are cut into
+    ///
+    /// The score is the number of distinct words shared by both texts divided by the number of
+    /// distinct words in either, from 0.0 for no shared word to 1.0 for the same set of words.
+    /// Word order and counts are ignored, and like [`stats`](#method.stats) whitespace and
+    /// punctuation aren't counted as words. Two texts without any word score 1.0.
+    ///
+    /// ## Params
+    ///
+    /// `a`, `b`: input texts
+    ///
+    /// `hmm`: enable HMM or not
+    pub fn token_similarity(&self, a: &str, b: &str, hmm: bool) -> f64 {
+        let words = |text| -> HashSet<&str> {
+            self.cut(text, hmm)
+                .into_iter()
+                .filter(|word| !self.is_separator(word))
+                .collect()
+        };
+        let (a, b) = (words(a), words(b));
+        let union = a.union(&b).count();
+        if union == 0 {
+            return 1.0;
+        }
+        a.intersection(&b).count() as f64 / union as f64
+    }
+
+    /// Cut the input text, return each distinct word once, in order of first occurrence
+    ///
+    /// Like [`word_counts`](#method.word_counts) whitespace and punctuation are words as well.
+    ///
+    /// ## Params
+    ///
+    /// `sentence`: input text
+    ///
+    /// `hmm`: enable HMM or not
+    pub fn cut_unique<'a>(&self, sentence: &'a str, hmm: bool) -> Vec<&'a str> {
+        let mut seen = HashSet::new();
+        self.cut(sentence, hmm)
+            .into_iter()
+            .filter(|word| seen.insert(*word))
+            .collect()
+    }
+
+    /// Cut the input text, return how many times each distinct word occurs
+    ///
+    /// Every word is counted, including whitespace and punctuation. Filter them out of the map,
+    /// or use [`cut_with_options`](#method.cut_with_options) and count the words yourself, if
+    /// they aren't wanted.
+    ///
+    /// ## Params
+    ///
+    /// `sentence`: input text
+    ///
+    /// `hmm`: enable HMM or not
+    pub fn word_counts<'a>(&self, sentence: &'a str, hmm: bool) -> HashMap<&'a str, usize> {
+        let mut counts = HashMap::new();
+        for word in self.cut(sentence, hmm) {
+            *counts.entry(word).or_insert(0) += 1;
+        }
+        counts
+    }
+
+    /// Cut the input text, return how many words there are of each length in bytes
+    ///
+    /// Byte lengths are the ones to size buffers with, a CJK char taking 3 bytes in UTF-8. Like
+    /// [`word_counts`](#method.word_counts), every word is counted, including whitespace and
+    /// punctuation.
+    ///
+    /// ## Params
+    ///
+    /// `sentence`: input text
+    ///
+    /// `hmm`: enable HMM or not
+    pub fn token_length_histogram(&self, sentence: &str, hmm: bool) -> HashMap<usize, usize> {
+        let mut histogram = HashMap::new();
+        for word in self.cut(sentence, hmm) {
+            *histogram.entry(word.len()).or_insert(0) += 1;
+        }
+        histogram
+    }
+
+    /// Cut the input text, return every run of `n` consecutive words
+    ///
+    /// The runs overlap, e.g. the bigrams of `a b c` are `a b` and `b c`. Nothing is returned
+    /// when the text has fewer than `n` words or `n` is 0.
+    ///
+    /// ## Params
+    ///
+    /// `sentence`: input text
+    ///
+    /// `n`: number of words in each run
+    ///
+    /// `hmm`: enable HMM or not
+    pub fn ngrams<'a>(&self, sentence: &'a str, n: usize, hmm: bool) -> Vec<Vec<&'a str>> {
+        if n == 0 {
+            return Vec::new();
+        }
+        self.cut(sentence, hmm).windows(n).map(|words| words.to_vec()).collect()
+    }
+
+    /// Cut the input text, return the words joined with `sep`
+    ///
+    /// The same as `cut(sentence, hmm).join(sep)`, but the output is allocated once with its
+    /// final size. Whitespace words are kept like in [`cut`](#method.cut).
+    ///
+    /// ## Params
+    ///
+    /// `sentence`: input text
+    ///
+    /// `hmm`: enable HMM or not
+    ///
+    /// `sep`: separator put between the words
+    pub fn cut_to_string(&self, sentence: &str, hmm: bool, sep: &str) -> String {
+        let words = self.cut(sentence, hmm);
+        let mut joined = String::with_capacity(sentence.len() + sep.len() * words.len().saturating_sub(1));
+        for (i, word) in words.into_iter().enumerate() {
+            if i > 0 {
+                joined.push_str(sep);
+            }
+            joined.push_str(word);
+        }
+        joined
+    }
+
+    /// Cut the input text, return owned words
+    ///
+    /// Convenient when the words have to outlive the input, like when passing them across
+    /// an FFI or WASM boundary.
+    ///
+    /// ## Params
+    ///
+    /// `sentence`: input text
+    ///
+    /// `hmm`: enable HMM or not
+    pub fn cut_owned(&self, sentence: &str, hmm: bool) -> Vec<String> {
+        self.cut(sentence, hmm).into_iter().map(String::from).collect()
+    }
+
+    /// Cut the input text which may be borrowed or owned, like after an optional normalization
+    ///
+    /// Borrowed text is cut into words borrowed from it, without allocating them. Owned text
+    /// is dropped once cut, so its words are returned owned like with
+    /// [`cut_owned`](#method.cut_owned).
+    ///
+    /// ## Params
+    ///
+    /// `sentence`: input text
+    ///
+    /// `hmm`: enable HMM or not
+    pub fn cut_cow<'a>(&self, sentence: Cow<'a, str>, hmm: bool) -> Vec<Cow<'a, str>> {
+        match sentence {
+            Cow::Borrowed(sentence) => self.cut(sentence, hmm).into_iter().map(Cow::Borrowed).collect(),
+            Cow::Owned(sentence) => self
+                .cut(&sentence, hmm)
+                .into_iter()
+                .map(|word| Cow::Owned(String::from(word)))
+                .collect(),
+        }
+    }
+
+    /// Cut the input text with and without HMM, return both cuts if they differ
+    ///
+    /// Returns `None` when HMM doesn't change the result, otherwise the words cut without HMM
+    /// followed by the words cut with HMM. Useful to find the sentences where HMM joined chars
+    /// the dict left apart.
+    ///
+    /// ## Params
+    ///
+    /// `sentence`: input text
+    pub fn cut_diff<'a>(&self, sentence: &'a str) -> Option<(Vec<&'a str>, Vec<&'a str>)> {
+        let without_hmm = self.cut(sentence, false);
+        let with_hmm = self.cut(sentence, true);
+        if without_hmm == with_hmm {
+            None
+        } else {
+            Some((without_hmm, with_hmm))
+        }
+    }
+
+    /// Cut the input text, return the words with the route score they contributed
+    ///
+    /// The score of a word cut from a han block is its log probability `ln(freq) - ln(total)`
+    /// adjusted by its weight set with [`set_word_weight`](#method.set_word_weight), as used by
+    /// the routing, the best route being the one with the highest sum of scores. Words which
+    /// aren't in the dict, like the ones recovered by HMM, count with the fallback frequency, 1
+    /// unless set by [`JiebaBuilder::fallback_freq`](struct.JiebaBuilder.html#method.fallback_freq).
+    /// Words outside of han blocks, like punctuations, aren't routed and score 0.
+    ///
+    /// ## Params
+    ///
+    /// `sentence`: input text
+    ///
+    /// `hmm`: enable HMM or not
+    pub fn explain<'a>(&self, sentence: &'a str, hmm: bool) -> Vec<(&'a str, f64)> {
+        self.cut(sentence, hmm)
+            .into_iter()
+            .map(|word| {
+                if !self.is_separator(word) {
+                    (word, self.token_score(word))
+                } else {
+                    (word, 0.0)
+                }
+            })
+            .collect()
+    }
+
+    /// Label every char of `fragment` with the state the HMM predicts for it
+    ///
+    /// A word is either a [`B`](enum.HmmStatus.html#variant.B) char, any number of
+    /// [`M`](enum.HmmStatus.html#variant.M) chars and an [`E`](enum.HmmStatus.html#variant.E)
+    /// char, or a single [`S`](enum.HmmStatus.html#variant.S) char. The whole fragment is
+    /// labeled at once without using the dict, while [`cut`](#method.cut) only runs the HMM on
+    /// the han chars the dict routing left as single chars.
+    ///
+    /// ## Params
+    ///
+    /// `fragment`: input text
+    pub fn hmm_tag(&self, fragment: &str) -> Vec<(char, HmmStatus)> {
+        hmm::tag(fragment)
+    }
+
+    /// Score a segmentation under the routing model, the higher the more likely
+    ///
+    /// The score is the sum of the log probabilities `ln(freq) - ln(total)` of the tokens, adjusted
+    /// by their weight set with [`set_word_weight`](#method.set_word_weight), which is what the
+    /// routing maximizes. Tokens which aren't in the dict count with the fallback frequency, see
+    /// [`JiebaBuilder::fallback_freq`](struct.JiebaBuilder.html#method.fallback_freq), dict
+    /// words with a zero frequency, which are never routed, score `-inf` and separators score 0
+    /// like in [`explain`](#method.explain). Segmentations of the same text can be compared this
+    /// way, the han blocks cut by [`cut`](#method.cut) without HMM score at least as high as any
+    /// other segmentation of them into dict words.
+    ///
+    /// ## Params
+    ///
+    /// `tokens`: the words of a segmentation
+    pub fn score_segmentation(&self, tokens: &[&str]) -> f64 {
+        tokens
+            .iter()
+            .filter(|token| !self.is_separator(token))
+            .map(|token| self.token_score(token))
+            .sum()
+    }
+
+    /// Cut the input text by forward maximum matching
+    ///
+    /// At every position the longest dict word starting there is taken, characters which
+    /// don't start any dict word are emitted on their own. Unlike [`cut`](#method.cut) the
+    /// word frequencies are not used, except that words with a zero frequency are never matched.
+    ///
+    /// ## Params
+    ///
+    /// `sentence`: input text
+    pub fn cut_leftmost_longest<'a>(&self, sentence: &'a str) -> Vec<&'a str> {
+        let mut words = Vec::new();
+        let mut byte_start = 0;
+        while let Some(ch) = sentence[byte_start..].chars().next() {
+            let byte_end = self
+                .cedar
+                .common_prefix_iter(&sentence[byte_start..])
+                .filter(|&(word_id, _)| self.records[word_id as usize].freq > 0)
+                .map(|(_, end_index)| byte_start + end_index + 1)
+                .last()
+                .unwrap_or(byte_start + ch.len_utf8());
+            words.push(&sentence[byte_start..byte_end]);
+            byte_start = byte_end;
+        }
+        words
+    }
+
+    /// Cut the input text, return the words of every block of the text separately
+    ///
+    /// The text is split into the blocks matched by the han regex and the blocks in between,
+    /// like punctuation and whitespace, before cutting; words never span two blocks. Each inner
+    /// vector holds the words of one block, in text order, so flattening them gives the same
+    /// words as [`cut`](#method.cut).
+    ///
+    /// ## Params
+    ///
+    /// `sentence`: input text
+    ///
+    /// `hmm`: enable HMM or not
+    pub fn cut_grouped<'a>(&self, sentence: &'a str, hmm: bool) -> Vec<Vec<&'a str>> {
+        SplitMatches::new(&self.re_han, sentence)
+            .map(|state| self.cut(state.into_str(), hmm))
+            .filter(|words| !words.is_empty())
+            .collect()
+    }
+
+    /// Split the input text into sentences and cut each of them
+    ///
+    /// Sentences are split by [`split_sentences`](fn.split_sentences.html), the terminators
+    /// stay attached to each sentence as its last words.
+    ///
+    /// ## Params
+    ///
+    /// `text`: input text
+    ///
+    /// `hmm`: enable HMM or not
+    pub fn cut_sentences<'a>(&self, text: &'a str, hmm: bool) -> Vec<Vec<&'a str>> {
+        split_sentences(text)
+            .into_iter()
+            .map(|sentence| self.cut(sentence, hmm))
+            .collect()
+    }
+
+    /// Cut the input text by reverse maximum matching
+    ///
+    /// Scanning from the end of the text, the longest dict word ending at the current position
+    /// is taken, characters which don't end any dict word are emitted on their own. This is the
+    /// right-to-left counterpart of [`cut_leftmost_longest`](#method.cut_leftmost_longest), and
+    /// likewise doesn't use the word frequencies except for never matching words with a zero
+    /// frequency.
+    ///
+    /// ## Params
+    ///
+    /// `sentence`: input text
+    pub fn cut_rmm<'a>(&self, sentence: &'a str) -> Vec<&'a str> {
+        let bounds: Vec<usize> = sentence
+            .char_indices()
+            .map(|x| x.0)
+            .chain(std::iter::once(sentence.len()))
+            .collect();
+
+        let mut words = Vec::new();
+        let mut end = bounds.len() - 1;
+        while end > 0 {
+            let start = (end.saturating_sub(self.longest_word_len)..end - 1)
+                .find(|&start| self.get_word_freq(&sentence[bounds[start]..bounds[end]], 0) > 0)
+                .unwrap_or(end - 1);
+            words.push(&sentence[bounds[start]..bounds[end]]);
+            end = start;
+        }
+        words.reverse();
+        words
+    }
+
+    /// Cut the input text, keeping each of the `protected` byte ranges as a single word
+    ///
+    /// Only the text between the protected ranges is cut. Empty ranges are ignored.
+    ///
+    /// ## Params
+    ///
+    /// `sentence`: input text
+    ///
+    /// `protected`: `(start, end)` byte ranges which must not be segmented
+    ///
+    /// `hmm`: enable HMM or not
+    ///
+    /// ## Panics
+    ///
+    /// Panics if the ranges overlap, or aren't on char boundaries of `sentence`
+    pub fn cut_with_protected<'a>(&self, sentence: &'a str, protected: &[(usize, usize)], hmm: bool) -> Vec<&'a str> {
+        let mut ranges: Vec<(usize, usize)> = protected.iter().copied().filter(|&(start, end)| start != end).collect();
+        ranges.sort_unstable();
+
+        let mut words = Vec::new();
+        let mut pos = 0;
+        for (start, end) in ranges {
+            assert!(pos <= start, "protected ranges overlap");
+            words.extend(self.cut(&sentence[pos..start], hmm));
+            words.push(&sentence[start..end]);
+            pos = end;
+        }
+        words.extend(self.cut(&sentence[pos..], hmm));
+        words
+    }
+
+    /// Cut the input text, preferring to cut at the given char positions
+    ///
+    /// A hint is a soft boundary: routing penalizes a word crossing it like an extra word of
+    /// frequency 1, so the text is cut there unless the word crossing it is much more likely
+    /// than any split. HMM never joins chars across a hint. Hints out of range or at the start or
+    /// end of the text are ignored.
+    ///
+    /// ## Params
+    ///
+    /// `sentence`: input text
+    ///
+    /// `hints`: char positions where a boundary is likely
+    ///
+    /// `hmm`: enable HMM or not
+    pub fn cut_with_boundary_hints<'a>(&self, sentence: &'a str, hints: &[usize], hmm: bool) -> Vec<&'a str> {
+        let mut byte_hints: Vec<usize> = Vec::with_capacity(hints.len());
+        let mut sorted_hints = hints.to_vec();
+        sorted_hints.sort_unstable();
+        sorted_hints.dedup();
+        let mut hint_iter = sorted_hints.into_iter().peekable();
+        for (char_index, (byte_index, _)) in sentence.char_indices().enumerate() {
+            if hint_iter.peek() == Some(&char_index) {
+                hint_iter.next();
+                if byte_index > 0 {
+                    byte_hints.push(byte_index);
+                }
+            }
+        }
+
+        let params = RouteParams {
+            hints: &byte_hints,
+            ..RouteParams::default()
+        };
+        self.cut_routed(sentence, hmm, params)
+    }
+
+    /// Cut the input text, scoring the words of the routes with `scorer`
+    ///
+    /// [`LogFreqScorer`](struct.LogFreqScorer.html) cuts like [`cut`](#method.cut), see
+    /// [`RouteScorer`](trait.RouteScorer.html) for what's scored. The weights set with
+    /// [`set_word_weight`](#method.set_word_weight) are still applied on top of the scores.
+    ///
+    /// ## Params
+    ///
+    /// `sentence`: input text
+    ///
+    /// `scorer`: score of a word in a route
+    ///
+    /// `hmm`: enable HMM or not
+    pub fn cut_with_scorer<'a, S: RouteScorer>(&self, sentence: &'a str, scorer: &S, hmm: bool) -> Vec<&'a str> {
+        let params = RouteParams {
+            scorer: Some(scorer),
+            ..RouteParams::default()
+        };
+        self.cut_routed(sentence, hmm, params)
+    }
+
+    /// Cut the han blocks of `sentence` routed with `params`, whose hints are sorted byte offsets
+    /// into `sentence`, and the other blocks like `cut` does
+    fn cut_routed<'a>(&self, sentence: &'a str, hmm: bool, params: RouteParams) -> Vec<&'a str> {
+        let options = CutOptions::default().hmm(hmm);
+        let mut words: Vec<&'a str> = Vec::new();
+        let mut buffers = RouteBuffers::with_capacity(sentence.len());
+        let mut offset = 0;
+        for state in SplitMatches::new(&self.re_han, sentence) {
+            let is_han = matches!(state, SplitState::Matched(_));
+            let block = state.into_str();
+            if is_han {
+                let block_hints: Vec<usize> = params
+                    .hints
+                    .iter()
+                    .filter(|&&hint| hint > offset && hint < offset + block.len())
+                    .map(|&hint| hint - offset)
+                    .collect();
+                let block_params = RouteParams {
+                    hints: &block_hints,
+                    ..params
+                };
+                let first = words.len();
+                if hmm {
+                    self.cut_dag_hmm(block, &mut words, &mut buffers, &options, block_params);
+                } else {
+                    self.cut_dag_no_hmm(block, &mut words, &mut buffers, &options, block_params);
+                }
+                // Routing only picks allowed words, the ones joined by HMM are split back
+                if let Some(allowed) = params.allowed {
+                    for word in words.split_off(first) {
+                        if word.chars().nth(1).is_none() || allowed(word) {
+                            words.push(word);
+                        } else {
+                            words.extend(word.char_indices().map(|(i, ch)| &word[i..i + ch.len_utf8()]));
+                        }
+                    }
+                }
+            } else {
+                words.extend(self.cut(block, false));
+            }
+            offset += block.len();
+        }
+        words
+    }
+
+    /// Cut the input text using only the dict words `allowed` accepts
+    ///
+    /// Routing treats the dict words longer than one char which `allowed` rejects as absent, so
+    /// every word returned is either accepted by `allowed` or a single char. Words joined by HMM
+    /// which `allowed` rejects are split back into chars. Otherwise the words are routed like
+    /// with [`cut`](#method.cut), so accepting every word gives the same result.
+    ///
+    /// ## Params
+    ///
+    /// `sentence`: input text
+    ///
+    /// `allowed`: whether a word may be returned, e.g. `|word| set.contains(word)`
+    ///
+    /// `hmm`: enable HMM or not
+    pub fn cut_restricted<'a, F: Fn(&str) -> bool>(&self, sentence: &'a str, allowed: F, hmm: bool) -> Vec<&'a str> {
+        let params = RouteParams {
+            allowed: Some(&allowed),
+            ..RouteParams::default()
+        };
+        self.cut_routed(sentence, hmm, params)
+    }
+
+    /// Return up to `k` segmentations of `sentence` with their log probabilities, best first
+    ///
+    /// This is a k-best variant of the route calculation used by `cut` without HMM, scoring the
+    /// words the same way, word weights and the fallback frequency included. The whole sentence
+    /// is scored as one block, without splitting it by the han and skip regexes first, so it is
+    /// meant for short runs of han characters.
+    ///
+    /// ## Params
+    ///
+    /// `sentence`: input text
+    ///
+    /// `k`: maximum number of segmentations to return
+    pub fn cut_nbest<'a>(&self, sentence: &'a str, k: usize) -> Vec<(Vec<&'a str>, f64)> {
+        if k == 0 {
+            return Vec::new();
+        }
+
+        let str_len = sentence.len();
+        let mut dag = StaticSparseDAG::with_size_hint(str_len);
+        self.dag(sentence, &mut dag, false, None, true);
+
+        // `best[i]` keeps the `k` best routes from byte `i` to the end of the sentence as
+        // `(log prob, byte end of the first word, index of the rest of the route in best[byte end])`
+        let mut best: Vec<Vec<(f64, usize, usize)>> = vec![Vec::new(); str_len + 1];
+        best[str_len].push((0.0, str_len, 0));
+
+        let mut prev_byte_start = str_len;
+        for byte_start in sentence.char_indices().map(|x| x.0).rev() {
+            let mut candidates = Vec::new();
+            for byte_end in dag.iter_edges(byte_start) {
+                // Scored like `calc` does, zero-frequency dict words are never picked
+                let log_prob = self.token_score(&sentence[byte_start..byte_end]);
+                if log_prob == f64::NEG_INFINITY {
+                    continue;
+                }
+                for (idx, &(rest, _, _)) in best[byte_end].iter().enumerate() {
+                    candidates.push((log_prob + rest, byte_end, idx));
+                }
+            }
+
+            if candidates.is_empty() {
+                let log_prob = (self.fallback_freq as f64).ln() - self.log_total();
+                for (idx, &(rest, _, _)) in best[prev_byte_start].iter().enumerate() {
+                    candidates.push((log_prob + rest, prev_byte_start, idx));
+                }
+            }
+
+            // Best first, ties broken towards the longer first word like in `calc`
+            candidates.sort_by(|x, y| cmp_route(&(y.0, y.1), &(x.0, x.1)));
+            candidates.truncate(k);
+            best[byte_start] = candidates;
+            prev_byte_start = byte_start;
+        }
+
+        best[0]
+            .iter()
+            .map(|&(log_prob, mut byte_end, mut idx)| {
+                let mut words = Vec::new();
+                let mut byte_start = 0;
+                while byte_start < str_len {
+                    words.push(&sentence[byte_start..byte_end]);
+                    let next = best[byte_end][idx];
+                    byte_start = byte_end;
+                    byte_end = next.1;
+                    idx = next.2;
+                }
+                (words, log_prob)
+            })
+            .collect()
+    }
+
+    /// Measure how ambiguous the segmentation of `sentence` is
+    ///
+    /// The score is the probability ratio of the runner-up segmentation to the best one, as
+    /// found by [`cut_nbest`](#method.cut_nbest), so under the same model as the routing of
+    /// [`cut`](#method.cut). It ranges from 0.0, when there is no other
+    /// segmentation or it is far less likely, to 1.0, when the two best segmentations are
+    /// equally likely.
+    ///
+    /// ## Params
+    ///
+    /// `sentence`: input text
+    pub fn ambiguity_score(&self, sentence: &str) -> f64 {
+        match self.cut_nbest(sentence, 2).as_slice() {
+            [(_, best), (_, runner_up)] => (runner_up - best).exp(),
+            _ => 0.0,
+        }
+    }
+
+    /// Cut the text read from `reader`, return an iterator over owned words
+    ///
+    /// The text is read in chunks of 8 KiB. Since a han block may go on in the next chunk, the
+    /// text read so far is only cut up to the start of its last han block, so the words are
+    /// the same as cutting the whole text at once, while only the text since the start of the
+    /// last han block is held in memory. Reading stops after the first I/O error, which is
+    /// yielded by the iterator; invalid UTF-8 is reported as an `InvalidData` error.
+    ///
+    /// ## Params
+    ///
+    /// `reader`: input text
+    ///
+    /// `hmm`: enable HMM or not
+    pub fn cut_reader<'a, R: BufRead + 'a>(
+        &'a self,
+        reader: R,
+        hmm: bool,
+    ) -> impl Iterator<Item = io::Result<String>> + 'a {
+        CutReader {
+            jieba: self,
+            reader,
+            hmm,
+            buf: Vec::new(),
+            words: VecDeque::new(),
+            done: false,
+        }
+    }
+
+    /// Cut the input text reusing the buffers of `ctx`
+    ///
+    /// Produces the same words as [cut](#method.cut), but the words are stored in `ctx`
+    /// and the returned slice borrows from it, so it's only valid until `ctx` is used again.
+    ///
+    /// ## Params
+    ///
+    /// `ctx`: scratch buffers, `sentence` must outlive the lifetime of the context
+    ///
+    /// `sentence`: input text
+    ///
+    /// `hmm`: enable HMM or not
+    pub fn cut_into<'a, 'c>(&self, ctx: &'c mut CutContext<'a>, sentence: &'a str, hmm: bool) -> &'c [&'a str] {
+        ctx.words.clear();
+        self.cut_with_context(ctx, sentence, &CutOptions::default().hmm(hmm));
+        &ctx.words
+    }
+
+    /// Cut the input text and append its words to `arena`, return the id of the sentence in it
+    ///
+    /// Produces the same words as [cut](#method.cut), stored as spans, see
+    /// [SegmentArena](struct.SegmentArena.html).
+    ///
+    /// ## Params
+    ///
+    /// `sentence`: input text
+    ///
+    /// `hmm`: enable HMM or not
+    ///
+    /// `arena`: where the words are appended, `sentence` must outlive the lifetime of the arena
+    pub fn cut_into_arena<'a>(&self, sentence: &'a str, hmm: bool, arena: &mut SegmentArena<'a>) -> usize {
+        let sentence_id = arena.sentences.len();
+        arena.sentences.push(sentence);
+        for word in self.cut_into(&mut arena.ctx, sentence, hmm) {
+            let byte_start = word.as_ptr() as usize - sentence.as_ptr() as usize;
+            arena.spans.push((sentence_id, byte_start, byte_start + word.len()));
+        }
+        sentence_id
+    }
+
+    /// Cut the input text, return all possible words
+    ///
+    /// ## Params
+    ///
+    /// `sentence`: input text
+    pub fn cut_all<'a>(&self, sentence: &'a str) -> Vec<&'a str> {
+        self.cut_with_options(sentence, &CutOptions::default().cut_all(true))
+    }
+
+    /// Cut the input text, return all possible words with their frequency, most frequent first
+    ///
+    /// The words are the ones of [`cut_all`](#method.cut_all). Words which aren't in the dict have
+    /// a frequency of 0, words with the same frequency keep the order of `cut_all`.
+    ///
+    /// ## Params
+    ///
+    /// `sentence`: input text
+    pub fn cut_all_ranked<'a>(&self, sentence: &'a str) -> Vec<(&'a str, usize)> {
+        let mut words: Vec<(&str, usize)> = self
+            .cut_all(sentence)
+            .into_iter()
+            .map(|word| (word, self.get_word_freq(word, 0)))
+            .collect();
+        words.sort_by_key(|&(_, freq)| std::cmp::Reverse(freq));
+        words
+    }
+
+    /// Cut the input text in search mode
+    ///
+    /// ## Params
+    ///
+    /// `sentence`: input text
+    ///
+    /// `hmm`: enable HMM or not
+    pub fn cut_for_search<'a>(&self, sentence: &'a str, hmm: bool) -> Vec<&'a str> {
+        self.cut_with_options(sentence, &CutOptions::default().search(true).hmm(hmm))
+    }
+
+    /// Find the 2-grams and 3-grams of `word` which are in the dict, as slices of `word`
+    ///
+    /// Instead of probing every gram, the dict matches are enumerated by walking the prefix
+    /// tree from each position of the word. Grams are ordered like search mode emits them,
+    /// 2-grams first and then 3-grams, each by position. Grams spanning the whole word are
+    /// skipped since the word itself is emitted by the caller.
+    fn search_grams<'a>(&self, word: &'a str, grams: &mut Vec<&'a str>) {
+        let char_count = word.chars().count();
+        if char_count <= 2 {
+            return;
+        }
+
+        for (byte_start, _) in word.char_indices() {
+            let haystack = &word[byte_start..];
+            for (_, end_index) in self.cedar.common_prefix_iter(haystack) {
+                let gram = &haystack[..=end_index];
+                let char_len = gram.chars().count();
+                if char_len > 3 || char_len >= char_count {
+                    break;
+                }
+                if char_len >= 2 {
+                    grams.push(gram);
+                }
+            }
+        }
+        grams.sort_by_key(|gram| gram.chars().count());
+    }
+
+    /// Tokenize
+    ///
+    /// ## Params
+    ///
+    /// `sentence`: input text
+    ///
+    /// `mode`: tokenize mode
+    ///
+    /// `hmm`: enable HMM or not
+    pub fn tokenize<'a>(&self, sentence: &'a str, mode: TokenizeMode, hmm: bool) -> Vec<Token<'a>> {
+        let words = self.cut(sentence, hmm);
+        let mut tokens = Vec::with_capacity(words.len());
+        let mut start = 0;
+        match mode {
+            TokenizeMode::Default => {
+                for word in words {
+                    let width = char_width(word);
+                    tokens.push(Token {
+                        word,
+                        start,
+                        end: start + width,
+                    });
+                    start += width;
+                }
+            }
+            TokenizeMode::Search => {
+                let mut grams = Vec::new();
+                for word in words {
+                    let width = char_width(word);
+                    self.search_grams(word, &mut grams);
+                    for gram in grams.drain(..) {
+                        // Counted in the same unit as the words, graphemes with the `grapheme` feature
+                        let byte_start = gram.as_ptr() as usize - word.as_ptr() as usize;
+                        let gram_start = start + char_width(&word[..byte_start]);
+                        tokens.push(Token {
+                            word: gram,
+                            start: gram_start,
+                            end: gram_start + char_width(gram),
+                        });
+                    }
+                    tokens.push(Token {
+                        word,
+                        start,
+                        end: start + width,
+                    });
+                    start += width;
+                }
+            }
+        }
+        tokens
+    }
+
+    /// Tokenize, with `start` and `end` counted in UTF-16 code units
+    ///
+    /// Same as [`tokenize`](#method.tokenize) for APIs indexing strings in UTF-16, like
+    /// JavaScript strings. A char outside of the Basic Multilingual Plane, like an emoji or a CJK
+    /// char of the Supplementary Ideographic Plane, counts 2.
+    ///
+    /// ## Params
+    ///
+    /// `sentence`: input text
+    ///
+    /// `mode`: tokenize mode
+    ///
+    /// `hmm`: enable HMM or not
+    pub fn tokenize_utf16<'a>(&self, sentence: &'a str, mode: TokenizeMode, hmm: bool) -> Vec<Token<'a>> {
+        // UTF-16 offset of every char boundary, indexed by byte offset
+        let mut utf16_offsets = vec![0; sentence.len() + 1];
+        let mut offset = 0;
+        for (byte_index, ch) in sentence.char_indices() {
+            utf16_offsets[byte_index] = offset;
+            offset += ch.len_utf16();
+        }
+        utf16_offsets[sentence.len()] = offset;
+
+        self.tokenize(sentence, mode, hmm)
+            .into_iter()
+            .map(|token| {
+                let byte_start = token.word.as_ptr() as usize - sentence.as_ptr() as usize;
+                Token {
+                    word: token.word,
+                    start: utf16_offsets[byte_start],
+                    end: utf16_offsets[byte_start + token.word.len()],
+                }
+            })
+            .collect()
+    }
+
+    /// Find every dict word occurring in the input text, overlapping matches included
+    ///
+    /// Unlike [`tokenize`](#method.tokenize) the matches don't depend on the segmentation, every
+    /// occurrence of a dict word is returned, ordered by start and then by length. Words with a
+    /// zero frequency are skipped. `start` and `end` are char offsets, like for `tokenize`.
+    ///
+    /// ## Params
+    ///
+    /// `text`: input text
+    pub fn find_words<'a>(&self, text: &'a str) -> Vec<Token<'a>> {
+        let mut tokens = Vec::new();
+        for (start, (byte_start, _)) in text.char_indices().enumerate() {
+            let haystack = &text[byte_start..];
+            for (word_id, end_index) in self.cedar.common_prefix_iter(haystack) {
+                if self.records[word_id as usize].freq == 0 {
+                    continue;
+                }
+                let word = &haystack[..=end_index];
+                tokens.push(Token {
+                    word,
+                    start,
+                    end: start + char_width(word),
+                });
+            }
+        }
+        tokens
+    }
+
+    /// For every char of the input text, find the longest dict word ending with it
+    ///
+    /// The result has one entry per char, indexed by char position (not byte offset, and not
+    /// grapheme clusters even with the `grapheme` feature): entry `i` is the longest dict word
+    /// whose last char is the `i`-th char of the text, or `None` if no dict word ends there.
+    /// Matches are the ones of [`find_words`](#method.find_words), so words with a zero
+    /// frequency are skipped.
+    ///
+    /// ## Params
+    ///
+    /// `sentence`: input text
+    pub fn longest_ending_at<'a>(&self, sentence: &'a str) -> Vec<Option<&'a str>> {
+        let mut longest = vec![None; sentence.chars().count()];
+        for (start, (byte_start, _)) in sentence.char_indices().enumerate() {
+            let haystack = &sentence[byte_start..];
+            for (word_id, end_index) in self.cedar.common_prefix_iter(haystack) {
+                if self.records[word_id as usize].freq == 0 {
+                    continue;
+                }
+                let word = &haystack[..=end_index];
+                // Starts are visited in order, so the first word ending at a char is the longest
+                let last = &mut longest[start + word.chars().count() - 1];
+                if last.is_none() {
+                    *last = Some(word);
+                }
+            }
+        }
+        longest
+    }
+
+    /// Check whether any dict word occurs in the input text, without segmenting it
+    ///
+    /// This stops at the first match of [`find_words`](#method.find_words), words with a zero
+    /// frequency are skipped as well. An instance built from a keyword list, e.g. with
+    /// [`from_records`](#method.from_records), makes a cheap filter for those keywords.
+    ///
+    /// ## Params
+    ///
+    /// `text`: input text
+    pub fn contains_any(&self, text: &str) -> bool {
+        text.char_indices().any(|(byte_start, _)| {
+            self.cedar
+                .common_prefix_iter(&text[byte_start..])
+                .any(|(word_id, _)| self.records[word_id as usize].freq > 0)
+        })
+    }
+
+    /// Tokenize, return owned tokens as `(word, start, end)`
+    ///
+    /// ## Params
+    ///
+    /// `sentence`: input text
+    ///
+    /// `mode`: tokenize mode
+    ///
+    /// `hmm`: enable HMM or not
+    pub fn tokenize_owned(&self, sentence: &str, mode: TokenizeMode, hmm: bool) -> Vec<(String, usize, usize)> {
+        self.tokenize(sentence, mode, hmm)
+            .into_iter()
+            .map(|token| (String::from(token.word), token.start, token.end))
+            .collect()
+    }
+
+    /// Tag the input text
+    ///
+    /// ## Params
+    ///
+    /// `sentence`: input text
+    ///
+    /// `hmm`: enable HMM or not
+    pub fn tag<'a>(&'a self, sentence: &'a str, hmm: bool) -> Vec<Tag> {
+        let words = self.cut(sentence, hmm);
+        words
+            .into_iter()
+            .map(|word| Tag {
+                word,
+                tag: self.lookup_tag(word),
+            })
+            .collect()
+    }
+
+    /// Tag the input text, merging the adjacent words with the same tag into one chunk
+    ///
+    /// A lightweight chunker: a run of nouns becomes a single noun phrase, tagged like its
+    /// words. Every tag is merged, including the `x` of punctuation and whitespace.
+    ///
+    /// ## Params
+    ///
+    /// `sentence`: input text
+    ///
+    /// `hmm`: enable HMM or not
+    pub fn tag_chunks<'a>(&'a self, sentence: &'a str, hmm: bool) -> Vec<Tag<'a>> {
+        merge_runs(sentence, self.cut(sentence, hmm), |word| Some(self.lookup_tag(word)))
+            .into_iter()
+            .map(|(word, tag)| Tag {
+                word,
+                tag: tag.unwrap_or_default(),
+            })
+            .collect()
+    }
+
+    /// Cut the input text once and return every word with its tag and position
+    ///
+    /// Words and tags are the same as with [`tag`](#method.tag), positions are the same as with
+    /// [`tokenize`](#method.tokenize) in default mode.
+    ///
+    /// ## Params
+    ///
+    /// `sentence`: input text
+    ///
+    /// `hmm`: enable HMM or not
+    pub fn analyze<'a>(&'a self, sentence: &'a str, hmm: bool) -> Vec<AnalyzedToken<'a>> {
+        let mut start = 0;
+        self.cut(sentence, hmm)
+            .into_iter()
+            .map(|word| {
+                let end = start + char_width(word);
+                let token = AnalyzedToken {
+                    word,
+                    tag: self.lookup_tag(word),
+                    start,
+                    end,
+                };
+                start = end;
+                token
+            })
+            .collect()
+    }
+
+    /// Tag many sentences, reusing the cut buffers between them
+    ///
+    /// The result is the same as calling [`tag`](#method.tag) on every sentence. With the `rayon`
+    /// feature enabled the sentences are tagged in parallel.
+    ///
+    /// ## Params
+    ///
+    /// `sentences`: input texts
+    ///
+    /// `hmm`: enable HMM or not
+    pub fn tag_batch<'a>(&'a self, sentences: &[&'a str], hmm: bool) -> Vec<Vec<Tag<'a>>> {
+        #[cfg(feature = "rayon")]
+        {
+            use rayon::prelude::*;
+
+            sentences
+                .par_iter()
+                .map_init(CutContext::new, |ctx, sentence| {
+                    self.tag_with_context(ctx, sentence, hmm)
+                })
+                .collect()
+        }
+
+        #[cfg(not(feature = "rayon"))]
+        {
+            let mut ctx = CutContext::new();
+            sentences
+                .iter()
+                .map(|sentence| self.tag_with_context(&mut ctx, sentence, hmm))
+                .collect()
+        }
+    }
+
+    fn tag_with_context<'a>(&'a self, ctx: &mut CutContext<'a>, sentence: &'a str, hmm: bool) -> Vec<Tag<'a>> {
+        self.cut_into(ctx, sentence, hmm)
+            .iter()
+            .map(|&word| Tag {
+                word,
+                tag: self.lookup_tag(word),
+            })
+            .collect()
+    }
+
+    /// Tag the input text, return the tagged words formatted as `word/tag` joined by spaces
+    ///
+    /// Handy to eyeball the output of [`tag`](#method.tag) in logs, e.g. "我们/r 中出/v".
+    ///
+    /// ## Params
+    ///
+    /// `sentence`: input text
+    ///
+    /// `hmm`: enable HMM or not
+    pub fn to_tagged_string(&self, sentence: &str, hmm: bool) -> String {
+        self.tag(sentence, hmm)
+            .iter()
+            .map(Tag::to_string)
+            .collect::<Vec<String>>()
+            .join(" ")
+    }
+
+    /// Tag the input text and replace the words `f` returns a replacement for
+    ///
+    /// `f` is called with every word and its tag, as returned by [`tag`](#method.tag). Words for
+    /// which it returns `None` are kept, so the text between replaced words stays intact.
+    ///
+    /// ## Params
+    ///
+    /// `sentence`: input text
+    ///
+    /// `hmm`: enable HMM or not
+    ///
+    /// `f`: called with a word and its tag, returns the replacement of the word if any
+    pub fn replace_words<F: Fn(&str, &str) -> Option<String>>(&self, sentence: &str, hmm: bool, f: F) -> String {
+        let mut replaced = String::with_capacity(sentence.len());
+        for t in self.tag(sentence, hmm) {
+            match f(t.word, t.tag) {
+                Some(replacement) => replaced.push_str(&replacement),
+                None => replaced.push_str(t.word),
+            }
+        }
+        replaced
+    }
+
+    /// Tag the input text, return owned `(word, tag)` pairs
+    ///
+    /// ## Params
+    ///
+    /// `sentence`: input text
+    ///
+    /// `hmm`: enable HMM or not
+    pub fn tag_owned(&self, sentence: &str, hmm: bool) -> Vec<(String, String)> {
+        self.tag(sentence, hmm)
+            .into_iter()
+            .map(|tag| (String::from(tag.word), String::from(tag.tag)))
+            .collect()
+    }
+
+    /// Tag the input text with options
+    ///
+    /// Words are looked up in the dict the same way as in [cut_with_options](#method.cut_with_options).
+    ///
+    /// ## Params
+    ///
+    /// `sentence`: input text
+    ///
+    /// `options`: cut options
+    pub fn tag_with_options<'a>(&'a self, sentence: &'a str, options: &CutOptions) -> Vec<Tag<'a>> {
+        let words = self.cut_with_options(sentence, options);
+        words
+            .into_iter()
+            .map(|word| {
+                let tag = if options.ignore_ascii_case {
+                    match self.word_id(&word.to_ascii_lowercase(), true) {
+                        Some(word_id) => &self.records[word_id].tag,
+                        None => self.lookup_tag(word),
+                    }
+                } else if options.lowercase_ascii {
+                    self.lookup_tag(&word.to_ascii_lowercase())
+                } else {
+                    self.lookup_tag(word)
+                };
+                Tag { word, tag }
+            })
+            .collect()
+    }
+
+    fn lookup_tag(&self, word: &str) -> &str {
+        if let Some((word_id, _, _)) = self.cedar.exact_match_search(word) {
+            return &self.records[word_id as usize].tag;
+        }
+        let mut eng = 0;
+        let mut m = 0;
+        for chr in word.chars() {
+            if chr.is_ascii_alphanumeric() {
+                eng += 1;
+                if chr.is_ascii_digit() {
+                    m += 1;
+                }
+            }
+        }
+        if eng == 0 {
+            "x"
+        } else if eng == m {
+            "m"
+        } else {
+            "eng"
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        split_sentences, AnalyzedToken, CutContext, CutOptions, Error, HmmStatus, Jieba, JiebaBuilder, LogFreqScorer,
+        MergeSingleChars, SegmentArena, SplitMatches, SplitState, Tag, TextStats, Token, TokenSource, TokenizeMode,
+        RE_HAN_DEFAULT, RE_SKIP_DEAFULT,
+    };
+    use regex::Regex;
+    use std::io::{BufReader, Cursor};
+
+    #[test]
+    fn test_init_with_default_dict() {
+        let _ = Jieba::new();
+    }
+
+    #[test]
+    fn test_split_matches() {
+        let re_han = &*RE_HAN_DEFAULT;
+        let splitter = SplitMatches::new(
+            &re_han,
+            "👪 PS: 我觉得开源有一个好处，就是能够敦促自己不断改进 👪，避免敞帚自珍",
+        );
+        for state in splitter {
+            match state {
+                SplitState::Matched(_) => {
+                    let block = state.into_str();
+                    assert_eq!(block.is_empty(), false);
+                }
+                SplitState::Unmatched(_) => {
+                    let block = state.into_str();
+                    assert_eq!(block.is_empty(), false);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_split_matches_against_unicode_sip() {
+        let re_han = &*RE_HAN_DEFAULT;
+        let splitter = SplitMatches::new(&re_han, "讥䶯䶰䶱䶲䶳䶴䶵𦡦");
+
+        let result: Vec<&str> = splitter.map(|x| x.into_str()).collect();
+        assert_eq!(result, vec!["讥䶯䶰䶱䶲䶳䶴䶵𦡦"]);
+    }
+
+    #[test]
+    fn test_cut_all() {
+        let jieba = Jieba::new();
+        let words = jieba.cut_all("abc网球拍卖会def");
+        assert_eq!(
+            words,
+            vec![
+                "abc",
+                "网",
+                "网球",
+                "网球拍",
+                "球",
+                "球拍",
+                "拍",
+                "拍卖",
+                "拍卖会",
+                "卖",
+                "会",
+                "def"
+            ]
+        );
+
+        // The cut_all from the python de-facto implementation is loosely defined,
+        // And the answer "我, 来到, 北京, 清华, 清华大学, 华大, 大学" from the python implementation looks weird since it drops the single character word even though it is part of the DAG candidates.
+        // For example, it includes "华大" but it doesn't include "清" and "学"
+        let words = jieba.cut_all("我来到北京清华大学");
+        assert_eq!(
+            words,
+            vec![
+                "我",
+                "来",
+                "来到",
+                "到",
+                "北",
+                "北京",
+                "京",
+                "清",
+                "清华",
+                "清华大学",
+                "华",
+                "华大",
+                "大",
+                "大学",
+                "学"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_cut_no_hmm() {
+        let jieba = Jieba::new();
+        let words = jieba.cut("abc网球拍卖会def", false);
+        assert_eq!(words, vec!["abc", "网球", "拍卖会", "def"]);
+    }
+
+    #[test]
+    fn test_cut_with_hmm() {
+        let jieba = Jieba::new();
+        let words = jieba.cut("我们中出了一个叛徒", false);
+        assert_eq!(words, vec!["我们", "中", "出", "了", "一个", "叛徒"]);
+        let words = jieba.cut("我们中出了一个叛徒", true);
+        assert_eq!(words, vec!["我们", "中出", "了", "一个", "叛徒"]);
+        let words = jieba.cut("我们中出了一个叛徒👪", true);
+        assert_eq!(words, vec!["我们", "中出", "了", "一个", "叛徒", "👪"]);
+
+        let words = jieba.cut("我来到北京清华大学", true);
+        assert_eq!(words, vec!["我", "来到", "北京", "清华大学"]);
+
+        let words = jieba.cut("他来到了网易杭研大厦", true);
+        assert_eq!(words, vec!["他", "来到", "了", "网易", "杭研", "大厦"]);
+    }
+
+    #[test]
+    fn test_cut_into() {
+        let jieba = Jieba::new();
+        let sentences = [
+            "我们中出了一个叛徒",
+            "abc网球拍卖会def",
+            "他来到了网易杭研大厦",
+            "我们中出了一个叛徒👪",
+            "",
+        ];
+        let mut ctx = CutContext::new();
+        for _ in 0..2 {
+            for sentence in sentences.iter() {
+                for &hmm in [false, true].iter() {
+                    let words = jieba.cut_into(&mut ctx, sentence, hmm);
+                    assert_eq!(words, &jieba.cut(sentence, hmm)[..]);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_contains_word() {
+        let mut jieba = Jieba::new();
+        assert!(jieba.contains_word("北京"));
+        assert!(!jieba.contains_word("北京xyz"));
+        assert!(!jieba.contains_word("北京大"));
+        assert!(!jieba.contains_word(""));
+
+        jieba.add_word("北京xyz", None, None);
+        assert!(jieba.contains_word("北京xyz"));
+    }
+
+    #[test]
+    fn test_words() {
+        let mut jieba = Jieba::new();
+        let (_, freq, tag) = jieba.words().find(|(word, _, _)| *word == "北京").unwrap();
+        assert!(freq > 0);
+        assert_eq!(tag, "ns");
+
+        let count = jieba.words().count();
+        jieba.add_word("小红书", Some(100), Some("nz"));
+        assert_eq!(jieba.words().count(), count + 1);
+        assert_eq!(jieba.words().last(), Some(("小红书", 100, "nz")));
+    }
+
+    #[test]
+    fn test_cut_with_options_lowercase_ascii() {
+        let mut jieba = Jieba::new();
+        jieba.add_word("rust", Some(1000), Some("nz"));
+
+        let options = CutOptions::default();
+        assert_eq!(jieba.cut_with_options("学rust", &options), vec!["学", "rust"]);
+        assert_eq!(jieba.cut_with_options("学Rust", &options), vec!["学", "Rust"]);
+
+        let options = CutOptions::default().lowercase_ascii(true);
+        for &sentence in ["我学rust", "我学Rust", "我学RUST", "我学rUsT"].iter() {
+            let words = jieba.cut_with_options(sentence, &options);
+            assert_eq!(words, vec!["我", "学", &sentence[6..]]);
+        }
+
+        let tags = jieba.tag_with_options("学RUST", &options);
+        assert_eq!(
+            tags[1],
+            Tag {
+                word: "RUST",
+                tag: "nz"
+            }
+        );
+        let tags = jieba.tag("学RUST", false);
+        assert_eq!(
+            tags[1],
+            Tag {
+                word: "RUST",
+                tag: "eng"
+            }
+        );
+    }
+
+    #[test]
+    fn test_cut_with_options() {
+        let jieba = Jieba::new();
+        let sentence = "我们中出了一个叛徒，南京市 长江大桥\r\nabc网球拍卖会def";
+
+        let options = CutOptions::default();
+        assert_eq!(jieba.cut_with_options(sentence, &options), jieba.cut(sentence, false));
+        let options = CutOptions::default().hmm(true);
+        assert_eq!(jieba.cut_with_options(sentence, &options), jieba.cut(sentence, true));
+        let options = CutOptions::default().cut_all(true);
+        assert_eq!(jieba.cut_with_options(sentence, &options), jieba.cut_all(sentence));
+        let options = CutOptions::default().cut_all(true).search(true).hmm(true);
+        assert_eq!(jieba.cut_with_options(sentence, &options), jieba.cut_all(sentence));
+        let options = CutOptions::default().search(true);
+        assert_eq!(
+            jieba.cut_with_options(sentence, &options),
+            jieba.cut_for_search(sentence, false)
+        );
+        let options = CutOptions::default().search(true).hmm(true);
+        assert_eq!(
+            jieba.cut_with_options(sentence, &options),
+            jieba.cut_for_search(sentence, true)
+        );
+
+        let options = CutOptions::default().hmm(true).keep_whitespace(false);
+        let words = jieba.cut_with_options(sentence, &options);
+        let expected: Vec<&str> = jieba
+            .cut(sentence, true)
+            .into_iter()
+            .filter(|word| !word.trim().is_empty())
+            .collect();
+        assert_eq!(words, expected);
+        assert!(!words.contains(&" "));
+        assert!(!words.contains(&"\r\n"));
+    }
+
+    #[test]
+    fn test_oov_words() {
+        let jieba = Jieba::new();
+        let sentence = "京华大酒店的张尧经理吃了一只北京烤鸭。";
+        assert_eq!(jieba.oov_words(sentence, true), vec!["张尧"]);
+        assert!(jieba.oov_words(sentence, false).is_empty());
+        assert_eq!(jieba.oov_words("我用iPhone13拍照", false), vec!["iPhone13"]);
+        assert!(jieba.oov_words("", true).is_empty());
+    }
+
+    #[test]
+    fn test_token_length_histogram() {
+        let jieba = Jieba::new();
+        let sentence = "我来到北京清华大学，hello";
+        let histogram = jieba.token_length_histogram(sentence, false);
+        assert_eq!(histogram[&3], 2);
+        assert_eq!(histogram[&5], 1);
+        assert_eq!(histogram[&6], 2);
+        assert_eq!(histogram[&12], 1);
+        assert_eq!(histogram.values().sum::<usize>(), jieba.cut(sentence, false).len());
+        assert!(jieba.token_length_histogram("", false).is_empty());
+    }
+
+    #[test]
+    fn test_word_counts() {
+        let jieba = Jieba::new();
+        let counts = jieba.word_counts("北京烤鸭，北京烤鸭真好吃", false);
+        assert_eq!(counts["北京烤鸭"], 2);
+        assert_eq!(counts["，"], 1);
+        assert_eq!(
+            counts.values().sum::<usize>(),
+            jieba.cut("北京烤鸭，北京烤鸭真好吃", false).len()
+        );
+        assert!(jieba.word_counts("", false).is_empty());
+    }
+
+    #[test]
+    fn test_cut_with_source() {
+        let jieba = Jieba::new();
+        let words = jieba.cut_with_source("张尧经理用iPhone13拍照， 真好", true);
+        assert_eq!(
+            words,
+            vec![
+                ("张尧", TokenSource::Hmm),
+                ("经理", TokenSource::Dictionary),
+                ("用", TokenSource::Dictionary),
+                ("iPhone13", TokenSource::Ascii),
+                ("拍照", TokenSource::Dictionary),
+                ("，", TokenSource::CharFallback),
+                (" ", TokenSource::Skip),
+                ("真", TokenSource::Dictionary),
+                ("好", TokenSource::Dictionary),
+            ]
+        );
+
+        let words = jieba.cut_with_source("张尧经理", false);
+        assert_eq!(words[0], ("张", TokenSource::Dictionary));
+
+        let words = jieba.cut_with_source("型号ＳＫ－ＩＩ", false);
+        assert_eq!(
+            words,
+            vec![
+                ("型号", TokenSource::Dictionary),
+                ("ＳＫ－ＩＩ", TokenSource::Fullwidth)
+            ]
+        );
+
+        for &hmm in &[false, true] {
+            let sentence = "我们中出了一个叛徒，张尧经理用iPhone13拍照";
+            let words: Vec<&str> = jieba.cut_with_source(sentence, hmm).into_iter().map(|x| x.0).collect();
+            assert_eq!(words, jieba.cut(sentence, hmm));
+        }
+
+        // "张尧" is a dict word too rare to be routed, HMM joins it
+        let jieba = Jieba::with_dict_str("我 1000\n是 1000\n张 1000\n尧 1000\n张尧 1\n").unwrap();
+        assert_eq!(
+            jieba.cut_with_source("我是张尧", true),
+            vec![
+                ("我", TokenSource::Dictionary),
+                ("是", TokenSource::Dictionary),
+                ("张尧", TokenSource::Hmm),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_cut_with_freq() {
+        let jieba = Jieba::new();
+        let words = jieba.cut_with_freq("他来到了网易杭研大厦", true);
+        let cut: Vec<&str> = words.iter().map(|(word, _)| *word).collect();
+        assert_eq!(cut, jieba.cut("他来到了网易杭研大厦", true));
+
+        let (_, freq) = words.iter().find(|(word, _)| *word == "来到").unwrap();
+        assert!(*freq > 1000);
+        let (_, freq) = words.iter().find(|(word, _)| *word == "杭研").unwrap();
+        assert_eq!(*freq, 0);
+        let (_, freq) = words.iter().find(|(word, _)| *word == "他").unwrap();
+        assert_eq!(*freq, jieba.get_word_freq("他", 0));
+
+        let words = jieba.cut_with_freq("南京市长江大桥", false);
+        assert_eq!(
+            words,
+            vec![
+                ("南京市", jieba.get_word_freq("南京市", 0)),
+                ("长江大桥", jieba.get_word_freq("长江大桥", 0)),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_builder_han_regex() {
+        let mut jieba = Jieba::new();
+        jieba.add_word("@jieba", Some(1000), None);
+        assert_eq!(jieba.cut("@jieba网球", false), vec!["@", "jieba", "网球"]);
+
+        let re_han = Regex::new(r"([\u{4E00}-\u{9FFF}a-zA-Z0-9+#&\._%@]+)").unwrap();
+        let mut jieba = JiebaBuilder::new().han_regex(re_han).build();
+        jieba.add_word("@jieba", Some(1000), None);
+        assert_eq!(jieba.cut("@jieba网球", false), vec!["@jieba", "网球"]);
+        assert_eq!(jieba.cut("@jieba 网球", true), vec!["@jieba", " ", "网球"]);
+    }
+
+    #[test]
+    fn test_builder_dict() {
+        let mut dict = BufReader::new("@jieba 1000 n\n网球 100 n\n拍卖会 100 n".as_bytes());
+        let re_han = Regex::new(r"([\u{4E00}-\u{9FFF}a-zA-Z0-9+#&\._%@]+)").unwrap();
+        let jieba = Jieba::builder().dict(&mut dict).han_regex(re_han).try_build().unwrap();
+        assert_eq!(jieba.records.len(), 3);
+        assert_eq!(jieba.cut("@jieba网球拍卖会", false), vec!["@jieba", "网球", "拍卖会"]);
+        assert_eq!(jieba.word_tag("@jieba"), Some("n"));
+
+        let mut dict = BufReader::new("网球 abc".as_bytes());
+        match Jieba::builder().dict(&mut dict).try_build() {
+            Err(Error::InvalidDictLine { line, .. }) => assert_eq!(line, 1),
+            _ => panic!("invalid dict entry should be reported"),
+        }
+
+        let mut dict = BufReader::new(&[0xff, 0xfe][..]);
+        assert!(matches!(
+            Jieba::builder().dict(&mut dict).try_build(),
+            Err(Error::Io(_))
+        ));
+
+        assert_eq!(
+            Jieba::builder().try_build().unwrap().records.len(),
+            Jieba::new().records.len()
+        );
+    }
+
+    #[test]
+    fn test_load_user_dict() {
+        let re_han = Regex::new(r"([\u{4E00}-\u{9FFF}a-zA-Z0-9+#&\._% ]+)").unwrap();
+        let mut jieba = JiebaBuilder::new().han_regex(re_han).build();
+        let mut dict = BufReader::new("New York ns\nLos Angeles 2000 ns\n北京烤鸭 100\nns\nHello World\n".as_bytes());
+        jieba.load_user_dict(&mut dict).unwrap();
+
+        assert_eq!(jieba.word_tag("New York"), Some("ns"));
+        assert_eq!(jieba.word_tag("Los Angeles"), Some("ns"));
+        assert_eq!(jieba.get_word_freq("Los Angeles", 0), 2000);
+        assert_eq!(jieba.get_word_freq("北京烤鸭", 0), 100);
+        assert!(jieba.contains_word("ns"));
+        assert!(jieba.contains_word("Hello World"));
+        assert_eq!(jieba.cut("我爱New York", false), vec!["我", "爱", "New York"]);
+
+        let mut dict = BufReader::new("New York 99999999999999999999999 ns".as_bytes());
+        match jieba.load_user_dict(&mut dict) {
+            Err(Error::InvalidDictLine { line, .. }) => assert_eq!(line, 1),
+            _ => panic!("invalid frequency should be reported"),
+        }
+    }
+
+    #[test]
+    fn test_builder_skip_regex() {
+        let jieba = JiebaBuilder::new().skip_regex(Regex::new(r"(\s|……)").unwrap()).build();
+        assert_eq!(jieba.cut("网球……拍卖会", false), vec!["网球", "……", "拍卖会"]);
+
+        let jieba = Jieba::new();
+        assert_eq!(jieba.cut("网球……拍卖会", false), vec!["网球", "…", "…", "拍卖会"]);
+    }
+
+    #[test]
+    fn test_builder_separators() {
+        let jieba = JiebaBuilder::new().separators(&['、', '-', '[', ']']).build();
+        assert_eq!(
+            jieba.cut("网球、拍卖会、、北京", true),
+            vec!["网球", "、", "拍卖会", "、", "、", "北京"]
+        );
+        assert_eq!(jieba.cut("[网球]-拍卖会", false), vec!["[", "网球", "]", "-", "拍卖会"]);
+        assert!(jieba.re_skip.is_match("]"));
+        assert!(!jieba.re_skip.is_match("a"));
+
+        let jieba = JiebaBuilder::new().separators(&[]).build_empty();
+        assert_eq!(jieba.re_skip.as_str(), RE_SKIP_DEAFULT.as_str());
+    }
+
+    #[test]
+    fn test_ngrams() {
+        let jieba = Jieba::new();
+        assert_eq!(
+            jieba.ngrams("我们中出了一个叛徒", 2, false),
+            vec![
+                vec!["我们", "中"],
+                vec!["中", "出"],
+                vec!["出", "了"],
+                vec!["了", "一个"],
+                vec!["一个", "叛徒"]
+            ]
+        );
+        assert_eq!(jieba.ngrams("我们中出了一个叛徒", 6, false).len(), 1);
+        assert!(jieba.ngrams("我们中出了一个叛徒", 7, false).is_empty());
+        assert!(jieba.ngrams("我们中出了一个叛徒", 0, false).is_empty());
+    }
+
+    #[test]
+    fn test_cut_to_string() {
+        let jieba = Jieba::new();
+        for sentence in &[
+            "我们中出了一个叛徒",
+            "小明硕士毕业于中国科学院计算所，后在日本京都大学深造",
+            "a",
+        ] {
+            let joined = jieba.cut_to_string(sentence, true, " ");
+            assert_eq!(joined, jieba.cut(sentence, true).join(" "));
+        }
+        assert_eq!(jieba.cut_to_string("我们中出了", false, "/"), "我们/中/出/了");
+        assert_eq!(jieba.cut_to_string("", false, "/"), "");
+    }
+
+    #[test]
+    fn test_owned() {
+        let jieba = Jieba::new();
+        let sentence = "我们中出了一个叛徒👪";
+        let words: Vec<String> = jieba
+            .cut(sentence, true)
+            .iter()
+            .map(|&word| String::from(word))
+            .collect();
+        assert_eq!(jieba.cut_owned(sentence, true), words);
+
+        let tokens = jieba.tokenize_owned(sentence, TokenizeMode::Search, true);
+        assert_eq!(tokens.len(), jieba.tokenize(sentence, TokenizeMode::Search, true).len());
+        assert_eq!(tokens[1], (String::from("中出"), 2, 4));
+
+        let tags = jieba.tag_owned(sentence, true);
+        assert_eq!(tags[0], (String::from("我们"), String::from("r")));
+    }
+
+    #[test]
+    fn test_explain() {
+        let jieba = Jieba::new();
+        let explained = jieba.explain("南京市长江大桥，", false);
+        let words: Vec<&str> = explained.iter().map(|(word, _)| *word).collect();
+        assert_eq!(words, vec!["南京市", "长江大桥", "，"]);
+        assert!(explained[0].1 < 0.0);
+        assert_eq!(explained[2].1, 0.0);
+
+        // The chosen route scores at least as high as the alternatives
+        let route_score: f64 = explained.iter().map(|(_, score)| score).sum();
+        let alternative: f64 = jieba.explain("南京", false)[0].1
+            + jieba.explain("市长", false)[0].1
+            + jieba.explain("江", false)[0].1
+            + jieba.explain("大桥", false)[0].1;
+        assert!(route_score > alternative);
+        let first = jieba.explain("南京", false)[0].1 + jieba.explain("市", false)[0].1;
+        assert!(explained[0].1 > first);
+    }
+
+    #[test]
+    fn test_with_dict_str() {
+        let dict = "我们 100 r\n\n叛徒 10 n\r\n一个 50 m";
+        let jieba = Jieba::with_dict_str(dict).unwrap();
+        let expected = Jieba::with_dict(&mut BufReader::new(dict.as_bytes())).unwrap();
+        assert_eq!(jieba.words().collect::<Vec<_>>(), expected.words().collect::<Vec<_>>());
+        assert_eq!(jieba.total, 160);
+        assert_eq!(
+            jieba.cut("我们中出了一个叛徒", false),
+            vec!["我们", "中", "出", "了", "一个", "叛徒"]
+        );
+
+        assert!(Jieba::with_dict_str("我们 abc").is_err());
+    }
+
+    #[test]
+    fn test_invalid_dict_line() {
+        let dict = "我们 100 r\n\n叛徒 ten n\n";
+        let err = Jieba::with_dict(&mut BufReader::new(dict.as_bytes())).unwrap_err();
+        match err {
+            Error::InvalidDictLine { line, ref message } => {
+                assert_eq!(line, 3);
+                assert!(message.starts_with("ten: "));
+            }
+            _ => panic!("unexpected error {:?}", err),
+        }
+        assert!(err.to_string().contains("line 3"));
+
+        let err = Jieba::with_dict_str(dict).unwrap_err();
+        assert!(err.to_string().contains("line 3"));
+        assert!(Jieba::try_new().is_ok());
+    }
+
+    #[test]
+    fn test_from_records() {
+        let records = vec![
+            (String::from("我们"), 100, String::from("r")),
+            (String::from("叛徒"), 10, String::from("n")),
+            (String::from("一个"), 50, String::from("m")),
+            (String::from("叛徒"), 20, String::from("v")),
+        ];
+        let mut jieba = Jieba::from_records(records);
+        assert_eq!(jieba.total, 170);
+        assert_eq!(jieba.longest_word_len, 2);
+        assert_eq!(jieba.word_tag("叛徒"), Some("n"));
+        assert_eq!(
+            jieba.cut("我们中出了一个叛徒", false),
+            vec!["我们", "中", "出", "了", "一个", "叛徒"]
+        );
+
+        jieba.extend(vec![(String::from("中出"), 30, String::new())]);
+        assert_eq!(jieba.total, 200);
+        assert_eq!(
+            jieba.cut("我们中出了一个叛徒", false),
+            vec!["我们", "中出", "了", "一个", "叛徒"]
+        );
+    }
+
+    #[test]
+    fn test_merge() {
+        let mut overlay = Jieba::empty();
+        overlay.add_word("小红书", Some(1000), Some("nz"));
+        overlay.add_word("北京", Some(7), Some("x"));
+
+        let mut jieba = Jieba::new();
+        let total = jieba.total;
+        let beijing = jieba.get_word_freq("北京", 0);
+        jieba.merge(&overlay);
+        assert_eq!(jieba.total, total + 1000 + 7 - beijing);
+        assert_eq!(jieba.get_word_freq("北京", 0), 7);
+        assert_eq!(jieba.word_tag("北京"), Some("ns"));
+        assert_eq!(jieba.word_tag("小红书"), Some("nz"));
+        assert_eq!(
+            jieba.cut("我在小红书上看到北京", false),
+            vec!["我", "在", "小红书", "上", "看到", "北京"]
+        );
+    }
+
+    #[test]
+    fn test_forbid_hmm_join() {
+        let mut jieba = Jieba::new();
+        assert_eq!(
+            jieba.cut("我们中出了一个叛徒", true),
+            vec!["我们", "中出", "了", "一个", "叛徒"]
+        );
+        jieba.forbid_hmm_join("中出");
+        assert_eq!(
+            jieba.cut("我们中出了一个叛徒", true),
+            vec!["我们", "中", "出", "了", "一个", "叛徒"]
+        );
+        assert_eq!(
+            jieba.cut("我们中出了一个叛徒", false),
+            vec!["我们", "中", "出", "了", "一个", "叛徒"]
+        );
+    }
+
+    #[test]
+    fn test_to_tagged_string() {
+        let jieba = Jieba::new();
+        assert_eq!(
+            Tag {
+                word: "北京",
+                tag: "ns"
+            }
+            .to_string(),
+            "北京/ns"
+        );
+
+        let sentence = "我们中出了一个叛徒";
+        let expected: Vec<String> = jieba
+            .tag(sentence, false)
+            .iter()
+            .map(|t| format!("{}/{}", t.word, t.tag))
+            .collect();
+        assert_eq!(jieba.to_tagged_string(sentence, false), expected.join(" "));
+        assert!(jieba.to_tagged_string(sentence, false).starts_with("我们/r "));
+        assert_eq!(jieba.to_tagged_string("", false), "");
+    }
+
+    #[test]
+    fn test_replace_words() {
+        let jieba = Jieba::new();
+        let sentence = "我是周杰伦，他是林俊杰。";
+        let redacted = jieba.replace_words(sentence, true, |_, tag| {
+            if tag == "nr" {
+                Some(String::from("[NAME]"))
+            } else {
+                None
+            }
+        });
+        assert_eq!(redacted, "我是[NAME]，他是[NAME]。");
+        assert_eq!(jieba.replace_words(sentence, true, |_, _| None), sentence);
+    }
+
+    #[test]
+    fn test_analyze() {
+        let jieba = Jieba::new();
+        let sentence = "今天纽约的天气真好啊，京华大酒店的张尧经理吃了一只北京烤鸭。";
+        for &hmm in &[false, true] {
+            let tokens = jieba.analyze(sentence, hmm);
+            let words: Vec<&str> = tokens.iter().map(|t| t.word).collect();
+            assert_eq!(words, jieba.cut(sentence, hmm));
+            let tags: Vec<&str> = tokens.iter().map(|t| t.tag).collect();
+            let expected_tags: Vec<&str> = jieba.tag(sentence, hmm).iter().map(|t| t.tag).collect();
+            assert_eq!(tags, expected_tags);
+            let offsets: Vec<(usize, usize)> = tokens.iter().map(|t| (t.start, t.end)).collect();
+            let expected_offsets: Vec<(usize, usize)> = jieba
+                .tokenize(sentence, TokenizeMode::Default, hmm)
+                .iter()
+                .map(|t| (t.start, t.end))
+                .collect();
+            assert_eq!(offsets, expected_offsets);
+        }
+        assert_eq!(
+            jieba.analyze("北京烤鸭", false),
+            vec![AnalyzedToken {
+                word: "北京烤鸭",
+                tag: "n",
+                start: 0,
+                end: 4
+            }]
+        );
+    }
+
+    #[test]
+    fn test_cut_empty_input() {
+        let jieba = Jieba::new();
+        for &hmm in &[false, true] {
+            assert!(jieba.cut("", hmm).is_empty());
+            assert!(jieba.cut_for_search("", hmm).is_empty());
+            assert!(jieba.tag("", hmm).is_empty());
+            assert!(jieba.tokenize("", TokenizeMode::Default, hmm).is_empty());
+            assert!(jieba.tokenize("", TokenizeMode::Search, hmm).is_empty());
+        }
+        assert!(jieba.cut_all("").is_empty());
+        assert!(jieba
+            .cut_with_options("", &CutOptions::default().ignore_ascii_case(true))
+            .is_empty());
+    }
+
+    #[test]
+    fn test_cut_whitespace_only() {
+        let jieba = Jieba::new();
+        for &hmm in &[false, true] {
+            assert_eq!(jieba.cut("   ", hmm), vec![" ", " ", " "]);
+            assert_eq!(jieba.cut("\t\n ", hmm), vec!["\t", "\n", " "]);
+            assert_eq!(jieba.cut_for_search("  ", hmm), vec![" ", " "]);
+            let tags = jieba.tag("  ", hmm);
+            assert!(tags.iter().all(|tag| tag.word == " " && tag.tag == "x"));
+            assert_eq!(tags.len(), 2);
+
+            let options = CutOptions::default().hmm(hmm).keep_whitespace(false);
+            assert!(jieba.cut_with_options("   ", &options).is_empty());
+        }
+        assert_eq!(jieba.cut_all("  "), vec![" ", " "]);
+    }
+
+    #[test]
+    fn test_cut_punctuation_only() {
+        let jieba = Jieba::new();
+        for &hmm in &[false, true] {
+            assert_eq!(jieba.cut("，。！？", hmm), vec!["，", "。", "！", "？"]);
+            assert_eq!(jieba.cut_for_search("，。", hmm), vec!["，", "。"]);
+        }
+        assert_eq!(jieba.cut_all("，。"), vec!["，", "。"]);
+    }
+
+    #[test]
+    fn test_cut_with_scorer() {
+        let jieba = Jieba::new();
+        let sentence = "南京市长江大桥";
+        assert_eq!(
+            jieba.cut_with_scorer(sentence, &LogFreqScorer, false),
+            jieba.cut(sentence, false)
+        );
+
+        let short_words = |word: &str, freq: usize, total: usize| {
+            let len = word.chars().count();
+            (freq as f64).ln() - (total as f64).ln() - if len > 2 { 100.0 } else { 0.0 }
+        };
+        assert_eq!(
+            jieba.cut_with_scorer(sentence, &short_words, false),
+            vec!["南京", "市", "长江", "大桥"]
+        );
+    }
+
+    #[test]
+    fn test_cut_with_boundary_hints() {
+        let jieba = Jieba::new();
+        let sentence = "南京市长江大桥";
+        assert_eq!(jieba.cut(sentence, false), vec!["南京市", "长江大桥"]);
+        assert_eq!(
+            jieba.cut_with_boundary_hints(sentence, &[], false),
+            jieba.cut(sentence, false)
+        );
+        for &hmm in &[false, true] {
+            assert_eq!(
+                jieba.cut_with_boundary_hints("南京市长", &[2], hmm),
+                vec!["南京", "市长"]
+            );
+            assert_eq!(
+                jieba.cut_with_boundary_hints(sentence, &[2, 4], hmm),
+                vec!["南京", "市长", "江", "大桥"]
+            );
+        }
+        assert_eq!(
+            jieba.cut_with_boundary_hints("我们，南京市长", &[0, 5, 100], false),
+            vec!["我们", "，", "南京", "市长"]
+        );
+    }
+
+    #[test]
+    fn test_token_similarity() {
+        let jieba = Jieba::new();
+        let a = "我们中出了一个叛徒";
+        assert_eq!(jieba.token_similarity(a, "我们中出了一个叛徒！", false), 1.0);
+        let near = jieba.token_similarity(a, "我们中间出了一个叛徒", false);
+        assert!(near > 0.5 && near < 1.0);
+        assert!(jieba.token_similarity(a, "北京烤鸭很好吃", false) < 0.1);
+        assert_eq!(jieba.token_similarity("", "，", false), 1.0);
+    }
+
+    #[test]
+    fn test_hmm_tag() {
+        let jieba = Jieba::new();
+        let fragment = "小明硕士毕业于中国科学院计算所";
+        let tags = jieba.hmm_tag(fragment);
+        assert_eq!(tags.len(), fragment.chars().count());
+
+        let mut words = Vec::new();
+        let mut word = String::new();
+        for (ch, status) in tags {
+            word.push(ch);
+            if let HmmStatus::E | HmmStatus::S = status {
+                words.push(std::mem::take(&mut word));
+            }
+        }
+        assert!(word.is_empty());
+        assert_eq!(words, vec!["小明", "硕士", "毕业于", "中国", "科学院", "计算", "所"]);
+    }
+
+    #[test]
+    fn test_attach_punctuation() {
+        let jieba = Jieba::new();
+        let sentence = "公司（北京）";
+        assert_eq!(jieba.cut(sentence, false), vec!["公司", "（", "北京", "）"]);
+        let options = CutOptions::default().attach_punctuation(true);
+        assert_eq!(jieba.cut_with_options(sentence, &options), vec!["公司", "（北京）"]);
+        assert_eq!(
+            jieba.cut_with_options("「（北京）」， （ 上海", &options),
+            vec!["「（北京）」", "，", " ", "（", " ", "上海"]
+        );
+        let search = options.search(true);
+        assert_eq!(
+            jieba.cut_with_options(sentence, &search),
+            jieba.cut_with_options(sentence, &CutOptions::default().search(true))
+        );
+    }
+
+    #[test]
+    fn test_write_dict() {
+        let mut jieba = Jieba::new();
+        jieba.add_word("南京市长", Some(1_000_000), Some("nr"));
+        jieba.add_word("龘靐", Some(10), None);
+        let mut dict = Vec::new();
+        jieba.write_dict(&mut dict).unwrap();
+        let dict = String::from_utf8(dict).unwrap();
+        assert!(dict.contains("南京市长 1000000 nr\n"));
+        assert!(dict.contains("龘靐 10\n"));
+
+        let reloaded = Jieba::with_dict(&mut BufReader::new(dict.as_bytes())).unwrap();
+        assert!(jieba.words().eq(reloaded.words()));
+        let sentence = "南京市长龘靐";
+        assert_eq!(reloaded.cut(sentence, true), jieba.cut(sentence, true));
+        assert_eq!(reloaded.cut(sentence, false), vec!["南京市长", "龘靐"]);
+    }
+
+    #[test]
+    fn test_fallback_freq() {
+        let dict = "南京 10\n南京市 2\n";
+        let jieba = Jieba::with_dict_str(dict).unwrap();
+        assert_eq!(jieba.cut("南京市长", false), vec!["南京市", "长"]);
+
+        let jieba = Jieba::builder()
+            .dict(&mut dict.as_bytes())
+            .fallback_freq(100)
+            .try_build()
+            .unwrap();
+        assert_eq!(jieba.cut("南京市长", false), vec!["南京", "市", "长"]);
+    }
+
+    #[test]
+    fn test_fallback_freq_single_word() {
+        let jieba = Jieba::builder()
+            .dict(&mut "南京 100\n南 200\n甲 100\n".as_bytes())
+            .fallback_freq(300)
+            .try_build()
+            .unwrap();
+        assert_eq!(jieba.cut("南京甲", false), vec!["南", "京", "甲"]);
+        assert_eq!(jieba.cut("南京", false), vec!["南", "京"]);
+    }
+
+    #[test]
+    fn test_tokenize_utf16() {
+        let jieba = Jieba::new();
+        let tokens = jieba.tokenize_utf16("北京👪烤鸭", TokenizeMode::Default, false);
+        assert_eq!(
+            tokens,
+            vec![
+                Token {
+                    word: "北京",
+                    start: 0,
+                    end: 2
+                },
+                Token {
+                    word: "👪",
+                    start: 2,
+                    end: 4
+                },
+                Token {
+                    word: "烤鸭",
+                    start: 4,
+                    end: 6
+                },
+            ]
+        );
+
+        let tokens = jieba.tokenize_utf16("𠀀中华人民共和国", TokenizeMode::Search, false);
+        let sentence_utf16: Vec<u16> = "𠀀中华人民共和国".encode_utf16().collect();
+        for token in &tokens {
+            let word_utf16: Vec<u16> = token.word.encode_utf16().collect();
+            assert_eq!(&sentence_utf16[token.start..token.end], &word_utf16[..]);
+        }
+        assert!(tokens.contains(&Token {
+            word: "中华人民共和国",
+            start: 2,
+            end: 9
+        }));
+    }
+
+    #[test]
+    fn test_is_separator() {
+        let jieba = Jieba::new();
+        assert!(jieba.is_separator("，"));
+        assert!(jieba.is_separator(" "));
+        assert!(!jieba.is_separator("网球"));
+        assert!(!jieba.is_separator("iPhone13"));
+        assert!(!jieba.is_separator(""));
+        assert!(!jieba.is_separator("ＳＫ－ＩＩ"));
+        assert!(jieba.is_separator("－"));
+        assert_eq!(jieba.oov_words("型号ＳＫ－ＩＩ", false), vec!["ＳＫ－ＩＩ"]);
+
+        let words: Vec<&str> = jieba
+            .cut("我们打网球， 你呢？", false)
+            .into_iter()
+            .filter(|word| !jieba.is_separator(word))
+            .collect();
+        assert_eq!(words, vec!["我们", "打网球", "你", "呢"]);
+    }
+
+    #[test]
+    fn test_tag_chunks() {
+        let jieba = Jieba::new();
+        let sentence = "我是拖拉机学院手扶拖拉机专业的。";
+        let tags: Vec<String> = jieba.tag(sentence, false).iter().map(Tag::to_string).collect();
+        assert_eq!(
+            tags,
+            vec![
+                "我/r",
+                "是/v",
+                "拖拉机/n",
+                "学院/n",
+                "手扶拖拉机/n",
+                "专业/n",
+                "的/uj",
+                "。/x"
+            ]
+        );
+        let chunks: Vec<String> = jieba.tag_chunks(sentence, false).iter().map(Tag::to_string).collect();
+        assert_eq!(
+            chunks,
+            vec!["我/r", "是/v", "拖拉机学院手扶拖拉机专业/n", "的/uj", "。/x"]
+        );
+        assert!(jieba.tag_chunks("", false).is_empty());
+    }
+
+    #[test]
+    fn test_fullwidth_product_codes() {
+        let jieba = Jieba::new();
+        for &hmm in &[false, true] {
+            assert_eq!(
+                jieba.cut("型号ＳＫ－ＩＩ和ＣＯ２，－－！", hmm),
+                vec!["型号", "ＳＫ－ＩＩ", "和", "ＣＯ２", "，", "－", "－", "！"]
+            );
+        }
+    }
+
+    #[test]
+    fn test_longest_ending_at() {
+        let jieba = Jieba::new();
+        let longest = jieba.longest_ending_at("长江大桥");
+        assert_eq!(longest.len(), 4);
+        assert_eq!(longest[0], Some("长"));
+        assert_eq!(longest[1], Some("长江"));
+        assert_eq!(longest[3], Some("长江大桥"));
+
+        let longest = jieba.longest_ending_at("，长江");
+        assert_eq!(longest, vec![None, Some("长"), Some("长江")]);
+        assert!(jieba.longest_ending_at("").is_empty());
+    }
+
+    #[test]
+    fn test_cut_cow() {
+        use std::borrow::Cow;
+
+        let jieba = Jieba::new();
+        let sentence = "我们中出了一个叛徒";
+        let words = jieba.cut_cow(Cow::Borrowed(sentence), false);
+        assert_eq!(words, jieba.cut(sentence, false));
+        assert!(words.iter().all(|word| matches!(word, Cow::Borrowed(_))));
+
+        let normalized: Cow<str> = Cow::Owned(String::from("我们中出了一个叛徒！").replace('！', "。"));
+        let words = jieba.cut_cow(normalized, false);
+        assert_eq!(words.last().map(|word| word.as_ref()), Some("。"));
+        assert_eq!(words[..words.len() - 1], jieba.cut(sentence, false)[..]);
+        assert!(words.iter().all(|word| matches!(word, Cow::Owned(_))));
+    }
+
+    #[test]
+    fn test_merge_single_chars() {
+        let jieba = Jieba::new();
+        let sentence = "我叫龘靐，代号x7";
+        let options = CutOptions::default().merge_ascii(false);
+        let cut = |kind| jieba.cut_with_options(sentence, &options.clone().merge_single_chars(kind));
+        assert_eq!(
+            cut(MergeSingleChars::None),
+            vec!["我", "叫", "龘", "靐", "，", "代号", "x", "7"]
+        );
+        assert_eq!(
+            cut(MergeSingleChars::Cjk),
+            vec!["我", "叫", "龘靐", "，", "代号", "x", "7"]
+        );
+        assert_eq!(
+            cut(MergeSingleChars::Ascii),
+            vec!["我", "叫", "龘", "靐", "，", "代号", "x7"]
+        );
+        assert_eq!(
+            cut(MergeSingleChars::Both),
+            vec!["我", "叫", "龘靐", "，", "代号", "x7"]
+        );
+
+        let hmm = CutOptions::default()
+            .hmm(true)
+            .merge_single_chars(MergeSingleChars::Cjk);
+        assert_eq!(jieba.cut_with_options("龘靐", &hmm), vec!["龘靐"]);
+    }
+
+    #[test]
+    fn test_score_segmentation() {
+        let mut jieba = Jieba::new();
+        let sentence = "南京市长江大桥，我们中出了一个叛徒";
+        let best = jieba.cut(sentence, false);
+        let worse = [
+            "南京", "市长", "江", "大桥", "，", "我们", "中", "出", "了", "一个", "叛徒",
+        ];
+        assert!(jieba.score_segmentation(&best) > jieba.score_segmentation(&worse));
+        assert_eq!(jieba.score_segmentation(&[]), 0.0);
+        assert_eq!(jieba.score_segmentation(&["，", " "]), 0.0);
+
+        let score: f64 = jieba.explain(sentence, false).iter().map(|&(_, score)| score).sum();
+        assert!((jieba.score_segmentation(&best) - score).abs() < 1e-9);
+
+        jieba.set_word_weight("市长", 1e9);
+        assert!(jieba.score_segmentation(&worse) > jieba.score_segmentation(&best));
+        let weighted: f64 = jieba.explain("南京市长", false).iter().map(|&(_, score)| score).sum();
+        assert!((jieba.score_segmentation(&["南京", "市长"]) - weighted).abs() < 1e-9);
+
+        jieba.add_word("市长", Some(0), None);
+        assert_eq!(jieba.score_segmentation(&worse), f64::NEG_INFINITY);
+    }
+
+    #[test]
+    fn test_cut_into_arena() {
+        let jieba = Jieba::new();
+        let sentences = ["南京市长江大桥", "", "我们中出了一个叛徒, iPhone13"];
+        let mut arena = SegmentArena::new();
+        for (i, sentence) in sentences.iter().enumerate() {
+            assert_eq!(jieba.cut_into_arena(sentence, true, &mut arena), i);
+        }
+
+        let words: Vec<&str> = sentences
+            .iter()
+            .flat_map(|sentence| jieba.cut(sentence, true))
+            .collect();
+        assert_eq!(arena.words().collect::<Vec<_>>(), words);
+        assert_eq!(arena.len(), words.len());
+        for (i, &(sentence_id, byte_start, byte_end)) in arena.spans().iter().enumerate() {
+            assert_eq!(&arena.sentence(sentence_id)[byte_start..byte_end], arena.word(i));
+            assert_ne!(sentence_id, 1);
+        }
+
+        arena.clear();
+        assert!(arena.is_empty());
+        assert_eq!(jieba.cut_into_arena("北京烤鸭", false, &mut arena), 0);
+        assert_eq!(arena.spans(), &[(0, 0, 12)]);
+    }
+
+    #[cfg(feature = "mmap")]
+    #[test]
+    fn test_from_mmap() {
+        use std::fs;
+
+        let path = std::env::temp_dir().join(format!("jieba-rs-test-mmap-{}.txt", std::process::id()));
+        fs::write(&path, "南京 100 ns\n市长 100 n\n长江大桥 200 ns\n").unwrap();
+        let jieba = Jieba::from_mmap(&path);
+        fs::write(&path, b"\xff\xfe").unwrap();
+        let invalid = Jieba::from_mmap(&path);
+        fs::remove_file(&path).unwrap();
+
+        let jieba = jieba.unwrap();
+        assert_eq!(jieba.cut("南京市长", false), vec!["南京", "市长"]);
+        assert_eq!(jieba.word_tag("长江大桥"), Some("ns"));
+        assert!(matches!(invalid, Err(Error::Io(ref err)) if err.kind() == std::io::ErrorKind::InvalidData));
+        assert!(matches!(Jieba::from_mmap(&path), Err(Error::Io(_))));
+    }
+
+    #[test]
+    fn test_collapse_punctuation() {
+        let jieba = Jieba::new();
+        let sentence = "他说——。。。结束";
+        assert_eq!(
+            jieba.cut(sentence, false),
+            vec!["他", "说", "—", "—", "。", "。", "。", "结束"]
+        );
+        let options = CutOptions::default().collapse_punctuation(true);
+        assert_eq!(
+            jieba.cut_with_options(sentence, &options),
+            vec!["他", "说", "——", "。。。", "结束"]
+        );
+        assert_eq!(
+            jieba.cut_with_options("好…。！！，  ？--", &options),
+            vec!["好", "…。", "！！", "，", " ", " ", "？", "--"]
+        );
+    }
+
+    #[test]
+    fn test_cut_diff() {
+        let jieba = Jieba::new();
+        assert_eq!(
+            jieba.cut_diff("我们中出了一个叛徒"),
+            Some((
+                vec!["我们", "中", "出", "了", "一个", "叛徒"],
+                vec!["我们", "中出", "了", "一个", "叛徒"]
+            ))
+        );
+        assert_eq!(jieba.cut_diff("南京市长江大桥"), None);
+        assert_eq!(jieba.cut_diff(""), None);
+    }
+
+    #[test]
+    fn test_cut_restricted() {
+        let jieba = Jieba::new();
+        let allowed: std::collections::HashSet<&str> = ["网球", "拍卖会"].iter().copied().collect();
+        assert_eq!(jieba.cut("网球拍卖会", false), vec!["网球", "拍卖会"]);
+        for &hmm in &[false, true] {
+            let words = jieba.cut_restricted("我们去网球拍卖会, 买了球拍。", |word| allowed.contains(word), hmm);
+            assert_eq!(words.concat(), "我们去网球拍卖会, 买了球拍。");
+            assert!(words.contains(&"网球"));
+            assert!(words.contains(&"拍卖会"));
+            assert!(words
+                .iter()
+                .all(|word| allowed.contains(word) || word.chars().count() == 1));
+        }
+
+        let allowed: std::collections::HashSet<&str> = ["球拍", "卖会"].iter().copied().collect();
+        assert_eq!(
+            jieba.cut_restricted("网球拍卖会", |word| allowed.contains(word), false),
+            vec!["网", "球拍", "卖", "会"]
+        );
+
+        // Accepting every word routes like cut, weights and fallback frequency included
+        let mut jieba = Jieba::builder().fallback_freq(1000).build();
+        jieba.set_word_weight("市长", 1e6);
+        for sentence in &["南京市长江大桥", "我们中出了一个叛徒", "他来到了网易杭研大厦"] {
+            for &hmm in &[false, true] {
+                assert_eq!(jieba.cut_restricted(sentence, |_| true, hmm), jieba.cut(sentence, hmm));
+            }
+        }
+    }
+
+    #[cfg(feature = "grapheme")]
+    #[test]
+    fn test_grapheme_fallback() {
+        let jieba = Jieba::new();
+        let family = "\u{1F468}\u{200D}\u{1F469}\u{200D}\u{1F467}";
+        let sentence = format!("我爱{}家", family);
+        assert_eq!(jieba.cut(&sentence, false), vec!["我", "爱", family, "家"]);
+        assert!(jieba.cut(&sentence, true).contains(&family));
+
+        let tokens = jieba.tokenize(&sentence, TokenizeMode::Default, false);
+        assert_eq!(
+            tokens[2],
+            Token {
+                word: family,
+                start: 2,
+                end: 3
+            }
+        );
+        assert_eq!(tokens[3].start, 3);
+
+        // "e" and the combining acute accent make a single grapheme
+        let re_han = Regex::new(r"([\u{4E00}-\u{9FFF}\u{0300}-\u{036F}a-z]+)").unwrap();
+        let mut dict = "e\u{301}中华 100\n中华 100\n".as_bytes();
+        let jieba = Jieba::builder().han_regex(re_han).build_with_dict(&mut dict).unwrap();
+        let tokens = jieba.tokenize("e\u{301}中华", TokenizeMode::Search, false);
+        assert_eq!(
+            tokens,
+            vec![
+                Token {
+                    word: "中华",
+                    start: 1,
+                    end: 3
+                },
+                Token {
+                    word: "e\u{301}中华",
+                    start: 0,
+                    end: 3
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_stats() {
+        let jieba = Jieba::new();
+        let stats = jieba.stats("我们中出了一个叛徒，一个叛徒！", false);
+        assert_eq!(
+            stats,
+            TextStats {
+                tokens: 8,
+                distinct_words: 6,
+                average_word_len: 13.0 / 8.0,
+                oov_ratio: 0.0,
+            }
+        );
+
+        let sentence = "他来到了网易杭研大厦";
+        let stats = jieba.stats(sentence, true);
+        let words = jieba.cut(sentence, true);
+        assert_eq!(stats.tokens, words.len());
+        assert_eq!(
+            stats.oov_ratio,
+            jieba.oov_words(sentence, true).len() as f64 / words.len() as f64
+        );
+        assert!(stats.oov_ratio > 0.0);
+
+        assert_eq!(jieba.stats("，。 ", false), TextStats::default());
+    }
+
+    #[test]
+    fn test_check_word_reachable() {
+        let mut jieba = Jieba::new();
+        assert!(jieba.check_word_reachable("北京烤鸭"));
+        assert!(!jieba.check_word_reachable("我们的"));
+
+        jieba.add_word("我们的", Some(1), None);
+        assert!(!jieba.check_word_reachable("我们的"));
+        assert_eq!(jieba.cut("我们的", false), vec!["我们", "的"]);
+
+        let freq = jieba.suggest_freq("我们的");
+        jieba.add_word("我们的", Some(freq), None);
+        assert!(jieba.check_word_reachable("我们的"));
+        assert_eq!(jieba.cut("我们的", false), vec!["我们的"]);
+    }
+
+    #[test]
+    fn test_set_word_weight() {
+        let mut jieba = Jieba::new();
+        assert_eq!(
+            jieba.cut("我们中出了一个叛徒", false),
+            vec!["我们", "中", "出", "了", "一个", "叛徒"]
+        );
+
+        jieba.set_word_weight("中出", 1e6);
+        assert_eq!(
+            jieba.cut("我们中出了一个叛徒", false),
+            vec!["我们", "中出", "了", "一个", "叛徒"]
+        );
+
+        jieba.set_word_weight("中出", 1.0);
+        jieba.set_word_weight("北京烤鸭", 1e-6);
+        assert_eq!(
+            jieba.cut("我们中出了一个叛徒", false),
+            vec!["我们", "中", "出", "了", "一个", "叛徒"]
+        );
+        assert_eq!(jieba.cut("北京烤鸭", false), vec!["北京", "烤鸭"]);
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_set_invalid_word_weight() {
+        let mut jieba = Jieba::empty();
+        jieba.set_word_weight("北京", 0.0);
+    }
+
+    #[test]
+    fn test_add_force_split() {
+        let mut jieba = Jieba::new();
+        assert_eq!(jieba.cut("我爱北京", false), vec!["我", "爱", "北京"]);
+        jieba.add_force_split("北京");
+        for &hmm in &[false, true] {
+            assert_eq!(jieba.cut("我爱北京", hmm), vec!["我", "爱", "北", "京"]);
+            assert_eq!(jieba.cut("北京", hmm), vec!["北", "京"]);
+            assert_eq!(jieba.cut("北京烤鸭", hmm), vec!["北京烤鸭"]);
+        }
+        assert!(jieba.contains_word("北京"));
+    }
+
+    #[test]
+    fn test_del_word_moves_last_word() {
+        let mut jieba = Jieba::with_dict_str("甲 10\n乙 40\n丙 20\n丁 30\nAbc 5\n").unwrap();
+        assert!(jieba.del_word("乙"));
+        let words: Vec<&str> = jieba.words().map(|(word, _, _)| word).collect();
+        assert_eq!(words, vec!["甲", "Abc", "丙", "丁"]);
+        assert_eq!(jieba.get_word_freq("丁", 0), 30);
+        assert_eq!(jieba.max_freq, 30);
+        assert_eq!(
+            jieba.cut_with_options("abc", &CutOptions::default().ignore_ascii_case(true)),
+            vec!["abc"]
+        );
+        assert_eq!(jieba.word_id("abc", true), Some(1));
+    }
+
+    #[test]
+    fn test_del_word() {
+        let mut jieba = Jieba::new();
+        let total = jieba.total;
+        let freq = jieba.records[jieba.cedar.exact_match_search("北京烤鸭").unwrap().0 as usize].freq;
+        assert!(jieba.del_word("北京烤鸭"));
+        assert!(!jieba.del_word("北京烤鸭"));
+        assert!(!jieba.contains_word("北京烤鸭"));
+        assert_eq!(jieba.total, total - freq);
+        assert_eq!(jieba.cut("北京烤鸭", false), vec!["北京", "烤鸭"]);
+
+        // Every remaining word still maps to its own record
+        for (word, _, _) in jieba.words().take(1000) {
+            let word_id = jieba.cedar.exact_match_search(word).unwrap().0 as usize;
+            assert_eq!(jieba.records[word_id].word, word);
+        }
+        let last = jieba.records.last().unwrap().word.clone();
+        assert!(jieba.del_word("我们"));
+        assert!(jieba.contains_word(&last));
+        let word_id = jieba.cedar.exact_match_search(&last).unwrap().0 as usize;
+        assert_eq!(jieba.records[word_id].word, last);
+
+        jieba.add_word("JavaScript", Some(100), None);
+        assert!(jieba.del_word("JavaScript"));
+        assert_eq!(
+            jieba.cut_with_options("javascript", &CutOptions::default().ignore_ascii_case(true)),
+            vec!["javascript"]
+        );
+    }
+
+    #[test]
+    fn test_reset_dict() {
+        let mut jieba = Jieba::new();
+        let total = jieba.total;
+        jieba.del_word("北京烤鸭");
+        jieba.add_word("今天纽约", Some(100_000), None);
+        assert_eq!(
+            jieba.cut("今天纽约的北京烤鸭", false),
+            vec!["今天纽约", "的", "北京", "烤鸭"]
+        );
+
+        jieba.reset_dict();
+        assert_eq!(jieba.total, total);
+        assert!(!jieba.contains_word("今天纽约"));
+        assert_eq!(
+            jieba.cut("今天纽约的北京烤鸭", false),
+            vec!["今天", "纽约", "的", "北京烤鸭"]
+        );
+    }
+
+    #[test]
+    fn test_add_word_with_count() {
+        let mut jieba = Jieba::new();
+        assert_eq!(
+            jieba.cut("我们中出了一个叛徒", false),
+            vec!["我们", "中", "出", "了", "一个", "叛徒"]
+        );
+        jieba.add_word("中出", Some(3), None);
+        assert_eq!(
+            jieba.cut("我们中出了一个叛徒", false),
+            vec!["我们", "中", "出", "了", "一个", "叛徒"]
+        );
+
+        let expected = 3 * (jieba.total / jieba.records.len());
+        assert_eq!(jieba.add_word_with_count("中出", 3, None), expected);
+        assert_eq!(
+            jieba.cut("我们中出了一个叛徒", false),
+            vec!["我们", "中出", "了", "一个", "叛徒"]
+        );
+
+        let mut jieba = Jieba::empty();
+        assert_eq!(jieba.add_word_with_count("中出", 3, None), 3);
+
+        let mut jieba = Jieba::with_dict_str("南京 10\n").unwrap();
+        assert_eq!(jieba.add_word_with_count("市长", usize::MAX / 2, None), usize::MAX);
+    }
+
+    #[test]
+    fn test_cut_leftmost_longest() {
+        let jieba = Jieba::new();
+        assert_eq!(jieba.cut_leftmost_longest("南京市长江大桥"), vec!["南京市", "长江大桥"]);
+        assert_eq!(
+            jieba.cut_leftmost_longest("研究生命的起源"),
+            vec!["研究生", "命", "的", "起源"]
+        );
+        assert_eq!(jieba.cut("研究生命的起源", false), vec!["研究", "生命", "的", "起源"]);
+        assert_eq!(jieba.cut_leftmost_longest("我 x"), vec!["我", " ", "x"]);
+        assert!(jieba.cut_leftmost_longest("").is_empty());
+    }
+
+    #[test]
+    fn test_cut_rmm() {
+        let jieba = Jieba::new();
+        assert_eq!(
+            jieba.cut_leftmost_longest("研究生命的起源"),
+            vec!["研究生", "命", "的", "起源"]
+        );
+        assert_eq!(jieba.cut_rmm("研究生命的起源"), vec!["研究", "生命", "的", "起源"]);
+        assert_eq!(jieba.cut_leftmost_longest("结合成分子"), vec!["结合", "成分", "子"]);
+        assert_eq!(jieba.cut_rmm("结合成分子"), vec!["结", "合成", "分子"]);
+        assert_eq!(jieba.cut_rmm("我 x"), vec!["我", " ", "x"]);
+        assert!(jieba.cut_rmm("").is_empty());
+    }
+
+    #[test]
+    fn test_cut_rmm_after_loading_short_dict() {
+        let mut jieba = Jieba::new();
+        jieba.load_dict(&mut "短 10".as_bytes()).unwrap();
+        assert_eq!(jieba.cut_rmm("中华人民共和国"), vec!["中华人民共和国"]);
+
+        let mut jieba = Jieba::new();
+        jieba.load_dict_str("短 10").unwrap();
+        assert_eq!(jieba.cut_rmm("中华人民共和国"), vec!["中华人民共和国"]);
+    }
+
+    #[test]
+    fn test_tag_batch() {
+        let jieba = Jieba::new();
+        let sentences = [
+            "我是拖拉机学院手扶拖拉机专业的。",
+            "",
+            "今天纽约的天气真好啊",
+            "iPhone13 很贵",
+        ];
+        for &hmm in &[false, true] {
+            let expected: Vec<Vec<Tag>> = sentences.iter().map(|s| jieba.tag(s, hmm)).collect();
+            assert_eq!(jieba.tag_batch(&sentences, hmm), expected);
+        }
+    }
+
+    #[test]
+    fn test_words_with_prefix() {
+        let mut jieba = Jieba::new();
+        let words = jieba.words_with_prefix("北京");
+        assert!(words.contains(&"北京"));
+        assert!(words.contains(&"北京大学"));
+        assert!(words.contains(&"北京烤鸭"));
+        assert!(words.iter().all(|word| word.starts_with("北京")));
+        assert!(words.windows(2).all(|pair| pair[0] < pair[1]));
+        assert_eq!(
+            words.len(),
+            jieba.words().filter(|(word, _, _)| word.starts_with("北京")).count()
+        );
+
+        assert!(jieba.words_with_prefix("北京烤鸭真").is_empty());
+        jieba.add_word("北京烤鸭真好吃", Some(10), None);
+        assert_eq!(jieba.words_with_prefix("北京烤鸭真"), vec!["北京烤鸭真好吃"]);
+        assert_eq!(Jieba::empty().words_with_prefix(""), Vec::<&str>::new());
+    }
+
+    #[test]
+    fn test_word_tag() {
+        let mut jieba = Jieba::new();
+        assert_eq!(jieba.word_tag("北京"), Some("ns"));
+        assert_eq!(jieba.word_tag("北京大"), None);
+        jieba.add_word("北京大", Some(10), None);
+        assert_eq!(jieba.word_tag("北京大"), None);
+    }
+
+    #[test]
+    fn test_cut_ascii_words() {
+        let jieba = Jieba::new();
+        for &hmm in &[false, true] {
+            assert!(jieba.cut("我用iPhone13拍照", hmm).ends_with(&["iPhone13", "拍照"]));
+            assert_eq!(jieba.cut("学C++和Rust", hmm), vec!["学", "C++", "和", "Rust"]);
+            assert_eq!(jieba.cut("版本v2.0发布", hmm), vec!["版本", "v2.0", "发布"]);
+            assert!(jieba.cut("涨了50%啊", hmm).ends_with(&["50%", "啊"]));
+        }
+        assert_eq!(jieba.cut("好..", false), vec!["好", ".", "."]);
+    }
+
+    #[test]
+    fn test_cut_with_options_ignore_ascii_case() {
+        let mut jieba = Jieba::new();
+        jieba.add_word("iPhone", Some(1000), Some("nz"));
+        jieba.add_word("拍照", Some(1000), None);
+        let options = CutOptions::default().ignore_ascii_case(true);
+        for sentence in &["我用iPhone拍照", "我用IPHONE拍照", "我用iphone拍照", "我用IpHoNe拍照"] {
+            let words = jieba.cut_with_options(sentence, &options);
+            assert_eq!(words, vec!["我", "用", &sentence[6..12], "拍照"]);
+
+            let words = jieba.cut_with_options(sentence, &options.clone().hmm(true));
+            assert!(words.ends_with(&[&sentence[6..12], "拍照"]));
+
+            let tags = jieba.tag_with_options(sentence, &options);
+            assert_eq!(tags[2].tag, "nz");
+        }
+
+        let words = jieba.cut_with_options("IPHONE", &options.clone().cut_all(true));
+        assert_eq!(words, vec!["IPHONE"]);
+        let options = CutOptions::default().lowercase_ascii(true);
+        assert_eq!(jieba.tag_with_options("我用IPHONE拍照", &options)[2].tag, "eng");
+        assert_eq!(jieba.word_id("iphone", false), None);
+    }
+
+    #[test]
+    fn test_cut_with_options_merge_ascii() {
+        let jieba = Jieba::new();
+        let options = CutOptions::default();
+        assert_eq!(jieba.cut_with_options("a1b2", &options), vec!["a1b2"]);
+        assert_eq!(
+            jieba.cut_with_options("学c#和a1b2", &options),
+            vec!["学", "c#", "和", "a1b2"]
+        );
+
+        let options = options.merge_ascii(false);
+        assert_eq!(jieba.cut_with_options("a1b2", &options), vec!["a", "1", "b", "2"]);
+        assert_eq!(
+            jieba.cut_with_options("学c#和a1b2", &options),
+            vec!["学", "c#", "和", "a", "1", "b", "2"]
+        );
+    }
+
+    #[test]
+    fn test_cut_sentences() {
+        let jieba = Jieba::new();
+        let sentences = jieba.cut_sentences("我们中出了一个叛徒。你们好吗？！", false);
+        assert_eq!(
+            sentences,
+            vec![
+                vec!["我们", "中", "出", "了", "一个", "叛徒", "。"],
+                vec!["你们好", "吗", "？", "！"]
+            ]
+        );
+
+        let sentences = jieba.cut_sentences("版本v2.0发布了. 好", false);
+        assert_eq!(
+            sentences,
+            vec![vec!["版本", "v2.0", "发布", "了", ".", " "], vec!["好"]]
+        );
+        assert!(jieba.cut_sentences("", false).is_empty());
+    }
+
+    #[test]
+    fn test_cut_unique() {
+        let jieba = Jieba::new();
+        assert_eq!(
+            jieba.cut_unique("纽约的天气，北京的天气，纽约", false),
+            vec!["纽约", "的", "天气", "，", "北京"]
+        );
+        assert!(jieba.cut_unique("", false).is_empty());
+    }
+
+    #[test]
+    fn test_cut_grouped() {
+        let jieba = Jieba::new();
+        assert_eq!(
+            jieba.cut_grouped("我们，中出了", false),
+            vec![vec!["我们"], vec!["，"], vec!["中", "出", "了"]]
+        );
+
+        let sentence = "今天纽约的天气真好啊，京华大酒店的张尧经理吃了一只北京烤鸭。 Rust!";
+        for &hmm in &[false, true] {
+            let groups = jieba.cut_grouped(sentence, hmm);
+            assert_eq!(groups.concat(), jieba.cut(sentence, hmm));
+        }
+        assert!(jieba.cut_grouped("", false).is_empty());
+    }
+
+    #[test]
+    fn test_split_sentences() {
+        assert_eq!(
+            split_sentences("今天天气真好。我们去公园吧！好不好？"),
+            vec!["今天天气真好。", "我们去公园吧！", "好不好？"]
+        );
+        assert_eq!(
+            split_sentences("他说：“我不去了。你们去吧！”然后就走了……真的吗?! Yes. v2.0 is out"),
+            vec![
+                "他说：“我不去了。你们去吧！”",
+                "然后就走了……",
+                "真的吗?! ",
+                "Yes. ",
+                "v2.0 is out"
+            ]
+        );
+        assert_eq!(
+            split_sentences("（见上文。）下一句。"),
+            vec!["（见上文。）", "下一句。"]
+        );
+        assert_eq!(split_sentences("“没有结尾。\n第二行。"), vec!["“没有结尾。\n第二行。"]);
+        assert!(split_sentences("").is_empty());
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use super::{Jieba, SplitMatches, SplitState, Tag, Token, TokenizeMode, RE_HAN_DEFAULT};
-    use std::io::BufReader;
+    #[test]
+    fn test_cut_with_options_search_full_word() {
+        let jieba = Jieba::new();
+        let options = CutOptions::default().search(true);
+        assert_eq!(
+            jieba.cut_with_options("中国科学院计算所", &options),
+            vec!["中国", "科学", "学院", "科学院", "中国科学院", "计算", "计算所"]
+        );
+        assert_eq!(
+            jieba.cut_with_options("中国科学院计算所", &options),
+            jieba.cut_for_search("中国科学院计算所", false)
+        );
+
+        let options = options.search_full_word(false);
+        assert_eq!(
+            jieba.cut_with_options("中国科学院计算所", &options),
+            vec!["中国", "科学", "学院", "科学院", "计算"]
+        );
+        assert_eq!(jieba.cut_with_options("我爱北京", &options), vec!["我", "爱", "北京"]);
+    }
 
     #[test]
-    fn test_init_with_default_dict() {
-        let _ = Jieba::new();
+    fn test_cut_with_options_max_match_chars() {
+        let jieba = Jieba::new();
+        let sentence = "我们中出了一个叛徒，中华人民共和国万岁！北京烤鸭真好吃";
+        let longest = jieba.longest_word_len;
+        for &hmm in &[false, true] {
+            let options = CutOptions::default().hmm(hmm);
+            let bounded = options.clone().max_match_chars(Some(longest));
+            assert_eq!(
+                jieba.cut_with_options(sentence, &bounded),
+                jieba.cut_with_options(sentence, &options)
+            );
+        }
+
+        let options = CutOptions::default().max_match_chars(Some(2));
+        let words = jieba.cut_with_options("中华人民共和国万岁", &options);
+        assert_eq!(words.concat(), "中华人民共和国万岁");
+        assert!(words.iter().all(|word| word.chars().count() <= 2));
+        assert!(words.contains(&"中华"));
+        assert_eq!(
+            jieba.cut_with_options("北京烤鸭", &CutOptions::default().max_match_chars(Some(0))),
+            vec!["北", "京", "烤", "鸭"]
+        );
+        assert_eq!(
+            jieba.cut_with_options("中华人民共和国", &options),
+            vec!["中华", "人民", "共和", "国"]
+        );
     }
 
     #[test]
-    fn test_split_matches() {
-        let re_han = &*RE_HAN_DEFAULT;
-        let splitter = SplitMatches::new(
-            &re_han,
-            "👪 PS: 我觉得开源有一个好处，就是能够敦促自己不断改进 👪，避免敞帚自珍",
+    fn test_cut_with_options_ascii_dict() {
+        let mut jieba = Jieba::new();
+        jieba.add_word("Rust", Some(10000), None);
+        jieba.add_word("lang", Some(10000), None);
+        assert_eq!(
+            jieba.cut_with_options("我用Rustlang", &CutOptions::default()),
+            vec!["我", "用", "Rust", "lang"]
         );
-        for state in splitter {
-            match state {
-                SplitState::Matched(_) => {
-                    let block = state.into_str();
-                    assert_eq!(block.is_empty(), false);
-                }
-                SplitState::Unmatched(_) => {
-                    let block = state.into_str();
-                    assert_eq!(block.is_empty(), false);
-                }
-            }
+
+        for &hmm in &[false, true] {
+            let options = CutOptions::default().hmm(hmm).ascii_dict(false);
+            assert!(jieba
+                .cut_with_options("我用Rustlang", &options)
+                .ends_with(&["Rustlang"]));
+            assert!(jieba.cut_with_options("买T恤", &options).ends_with(&["T", "恤"]));
         }
+        let options = CutOptions::default().ascii_dict(false).merge_ascii(false);
+        assert_eq!(jieba.cut_with_options("Rust", &options), vec!["R", "u", "s", "t"]);
     }
 
     #[test]
-    fn test_split_matches_against_unicode_sip() {
-        let re_han = &*RE_HAN_DEFAULT;
-        let splitter = SplitMatches::new(&re_han, "讥䶯䶰䶱䶲䶳䶴䶵𦡦");
+    fn test_cut_with_options_keep_numbers() {
+        let jieba = Jieba::new();
+        let options = CutOptions::default().keep_numbers(true);
+        assert_eq!(jieba.cut_with_options("增资4.3亿元", &options), vec!["增资", "4.3亿元"]);
+        assert_eq!(jieba.cut_with_options("两千零五年", &options), vec!["两千零五年"]);
+        assert_eq!(
+            jieba.cut_with_options("由7000万元增加到5亿元", &options),
+            vec!["由", "7000万元", "增加", "到", "5亿元"]
+        );
+        assert_eq!(
+            jieba.cut_with_options("涨了50%，万一呢", &options),
+            vec!["涨", "了", "50%", "，", "万一", "呢"]
+        );
+        assert_eq!(
+            jieba.cut_with_options("我用iPhone13拍照", &options),
+            jieba.cut("我用iPhone13拍照", false)
+        );
+        assert_ne!(jieba.cut("两千零五年", false), vec!["两千零五年"]);
+    }
 
-        let result: Vec<&str> = splitter.map(|x| x.into_str()).collect();
-        assert_eq!(result, vec!["讥䶯䶰䶱䶲䶳䶴䶵𦡦"]);
+    #[test]
+    fn test_cut_with_options_max_token_chars() {
+        let jieba = Jieba::new();
+        let sentence = "我是中华人民共和国公民，在中国科学院计算所工作";
+        let options = CutOptions::default().max_token_chars(Some(3));
+        let words = jieba.cut_with_options(sentence, &options);
+        assert!(words.iter().all(|word| word.chars().count() <= 3));
+        assert_eq!(words.concat(), sentence);
+        assert_eq!(words[2..5], ["中华", "人民", "共和国"]);
+
+        let options = CutOptions::default().hmm(true).search(true).max_token_chars(Some(1));
+        let words = jieba.cut_with_options(sentence, &options);
+        assert!(words.iter().all(|word| word.chars().count() == 1));
+
+        let options = CutOptions::default().max_token_chars(Some(7));
+        assert_eq!(jieba.cut_with_options(sentence, &options), jieba.cut(sentence, false));
     }
 
     #[test]
-    fn test_cut_all() {
+    fn test_cut_with_protected() {
         let jieba = Jieba::new();
-        let words = jieba.cut_all("abc网球拍卖会def");
+        let sentence = "我们中出了一个叛徒http://example.com/网球拍卖会";
+        let start = sentence.find("http").unwrap();
+        let end = start + "http://example.com/网球".len();
+        let words = jieba.cut_with_protected(sentence, &[(start, end)], false);
         assert_eq!(
             words,
             vec![
-                "abc",
-                "网",
-                "网球",
-                "网球拍",
-                "球",
-                "球拍",
-                "拍",
-                "拍卖",
-                "拍卖会",
-                "卖",
-                "会",
-                "def"
+                "我们",
+                "中",
+                "出",
+                "了",
+                "一个",
+                "叛徒",
+                "http://example.com/网球",
+                "拍卖会"
             ]
         );
 
-        // The cut_all from the python de-facto implementation is loosely defined,
-        // And the answer "我, 来到, 北京, 清华, 清华大学, 华大, 大学" from the python implementation looks weird since it drops the single character word even though it is part of the DAG candidates.
-        // For example, it includes "华大" but it doesn't include "清" and "学"
-        let words = jieba.cut_all("我来到北京清华大学");
+        let words = jieba.cut_with_protected(sentence, &[(0, 6), (3, 3)], false);
+        assert_eq!(words[0], "我们");
         assert_eq!(
-            words,
-            vec![
-                "我",
-                "来",
-                "来到",
-                "到",
-                "北",
-                "北京",
-                "京",
-                "清",
-                "清华",
-                "清华大学",
-                "华",
-                "华大",
-                "大",
-                "大学",
-                "学"
-            ]
+            jieba.cut_with_protected(sentence, &[], false),
+            jieba.cut(sentence, false)
         );
     }
 
     #[test]
-    fn test_cut_no_hmm() {
+    #[should_panic(expected = "protected ranges overlap")]
+    fn test_cut_with_protected_overlap() {
         let jieba = Jieba::new();
-        let words = jieba.cut("abc网球拍卖会def", false);
-        assert_eq!(words, vec!["abc", "网球", "拍卖会", "def"]);
+        jieba.cut_with_protected("我们中出了一个叛徒", &[(0, 6), (3, 9)], false);
     }
 
     #[test]
-    fn test_cut_with_hmm() {
+    fn test_cut_nbest() {
         let jieba = Jieba::new();
-        let words = jieba.cut("我们中出了一个叛徒", false);
-        assert_eq!(words, vec!["我们", "中", "出", "了", "一个", "叛徒"]);
-        let words = jieba.cut("我们中出了一个叛徒", true);
-        assert_eq!(words, vec!["我们", "中出", "了", "一个", "叛徒"]);
-        let words = jieba.cut("我们中出了一个叛徒👪", true);
-        assert_eq!(words, vec!["我们", "中出", "了", "一个", "叛徒", "👪"]);
+        let sentence = "南京市长江大桥";
+        let nbest = jieba.cut_nbest(sentence, 3);
+        assert_eq!(nbest.len(), 3);
+        assert_eq!(nbest[0].0, jieba.cut(sentence, false));
+        let score: f64 = jieba.explain(sentence, false).iter().map(|x| x.1).sum();
+        assert!((nbest[0].1 - score).abs() < 1e-9);
+        for pair in nbest.windows(2) {
+            assert_ne!(pair[0].0, pair[1].0);
+            assert!(pair[0].1 >= pair[1].1);
+        }
+        for (words, _) in &nbest {
+            assert_eq!(words.concat(), sentence);
+        }
 
-        let words = jieba.cut("我来到北京清华大学", true);
-        assert_eq!(words, vec!["我", "来到", "北京", "清华大学"]);
+        assert!(jieba.cut_nbest(sentence, 0).is_empty());
+        assert_eq!(jieba.cut_nbest("", 2), vec![(vec![], 0.0)]);
 
-        let words = jieba.cut("他来到了网易杭研大厦", true);
-        assert_eq!(words, vec!["他", "来到", "了", "网易", "杭研", "大厦"]);
+        let mut jieba = Jieba::builder().fallback_freq(100_000).build();
+        jieba.set_word_weight("市长", 1e6);
+        jieba.set_word_weight("大桥", 1e-6);
+        for sentence in &["南京市长江大桥", "我们中出了一个叛徒", "乒乓球拍卖完了", "讥䶯䶰䶱"]
+        {
+            assert_eq!(jieba.cut_nbest(sentence, 1)[0].0, jieba.cut(sentence, false));
+        }
+    }
+
+    #[test]
+    fn test_ambiguity_score() {
+        let jieba = Jieba::new();
+        let clear = jieba.ambiguity_score("南京市长江大桥");
+        let garden_path = jieba.ambiguity_score("乒乓球拍卖完了");
+        assert!(clear > 0.0);
+        assert!(clear < garden_path);
+        assert!(garden_path < 1.0);
+        assert!(jieba.ambiguity_score("研究生命的起源") > clear);
+        assert_eq!(jieba.ambiguity_score("我"), 0.0);
+        assert_eq!(jieba.ambiguity_score(""), 0.0);
+
+        // Weighting one reading up makes the sentence clear-cut
+        let mut jieba = Jieba::new();
+        jieba.set_word_weight("拍卖", 1e6);
+        assert!(jieba.ambiguity_score("乒乓球拍卖完了") < garden_path / 1e3);
+    }
+
+    #[test]
+    fn test_cut_reader() {
+        static WEICHENG_TXT: &str = include_str!("../examples/weicheng/src/weicheng.txt");
+        let jieba = Jieba::new();
+        let words: Vec<String> = jieba
+            .cut_reader(Cursor::new(WEICHENG_TXT), true)
+            .collect::<Result<_, _>>()
+            .unwrap();
+        assert_eq!(words, jieba.cut(WEICHENG_TXT, true));
+
+        // A single han block longer than a chunk is never split
+        let text = "中华人民共和国".repeat(1000);
+        let words: Vec<String> = jieba
+            .cut_reader(Cursor::new(text.as_str()), false)
+            .collect::<Result<_, _>>()
+            .unwrap();
+        assert_eq!(words, jieba.cut(&text, false));
+
+        let mut words = jieba.cut_reader(Cursor::new(&b"\xe6\x88\x91\xff"[..]), true);
+        assert!(words.next().unwrap().is_err());
+        assert!(words.next().is_none());
     }
 
     #[test]
@@ -917,6 +5527,18 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_cut_all_ranked() {
+        let jieba = Jieba::new();
+        let words = jieba.cut_all_ranked("清华大学，好");
+        let rank = |word: &str| words.iter().position(|x| x.0 == word).unwrap();
+        assert!(rank("清华大学") < rank("华大"));
+        assert!(rank("大学") < rank("清华"));
+        assert_eq!(words.last(), Some(&("，", 0)));
+        assert!(words.windows(2).all(|pair| pair[0].1 >= pair[1].1));
+        assert_eq!(words.len(), jieba.cut_all("清华大学，好").len());
+    }
+
     #[test]
     fn test_cut_for_search() {
         let jieba = Jieba::new();
@@ -1069,6 +5691,109 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_find_words() {
+        let jieba = Jieba::new();
+        let words: Vec<&str> = jieba.find_words("网球拍卖会").into_iter().map(|t| t.word).collect();
+        assert_eq!(words, jieba.cut_all("网球拍卖会"));
+        for word in &["网球", "网球拍", "球拍", "拍卖", "拍卖会"] {
+            assert!(words.contains(word));
+        }
+
+        let tokens = jieba.find_words("我爱网球");
+        assert!(tokens.contains(&Token {
+            word: "网球",
+            start: 2,
+            end: 4
+        }));
+        assert!(tokens.windows(2).all(|pair| pair[0].start <= pair[1].start));
+        assert!(jieba.find_words("").is_empty());
+    }
+
+    #[test]
+    fn test_contains_any() {
+        let jieba = Jieba::new();
+        assert!(jieba.contains_any("我爱北京"));
+        assert!(!jieba.contains_any("𠀀𠀁"));
+        assert!(!jieba.contains_any(""));
+
+        let keywords = vec![
+            (String::from("烤鸭"), 1, String::new()),
+            (String::from("纽约"), 1, String::new()),
+        ];
+        let filter = Jieba::from_records(keywords);
+        assert!(filter.contains_any("今天吃了一只北京烤鸭"));
+        assert!(!filter.contains_any("今天天气真好"));
+    }
+
+    #[test]
+    fn test_tokenize_search_offsets() {
+        let jieba = Jieba::new();
+        let tokens = jieba.tokenize("中国科学院计算所", TokenizeMode::Search, false);
+        assert_eq!(
+            tokens,
+            vec![
+                Token {
+                    word: "中国",
+                    start: 0,
+                    end: 2
+                },
+                Token {
+                    word: "科学",
+                    start: 2,
+                    end: 4
+                },
+                Token {
+                    word: "学院",
+                    start: 3,
+                    end: 5
+                },
+                Token {
+                    word: "科学院",
+                    start: 2,
+                    end: 5
+                },
+                Token {
+                    word: "中国科学院",
+                    start: 0,
+                    end: 5
+                },
+                Token {
+                    word: "计算",
+                    start: 5,
+                    end: 7
+                },
+                Token {
+                    word: "计算所",
+                    start: 5,
+                    end: 8
+                },
+            ]
+        );
+
+        // The last 2-gram and 3-gram of a word end exactly where the word ends
+        let tokens = jieba.tokenize("我在中华人民共和国", TokenizeMode::Search, false);
+        assert!(tokens.contains(&Token {
+            word: "共和国",
+            start: 6,
+            end: 9
+        }));
+        assert!(tokens.contains(&Token {
+            word: "中华人民共和国",
+            start: 2,
+            end: 9
+        }));
+
+        let sentence = "小明硕士毕业于中国科学院计算所，后在日本京都大学深造";
+        let chars: Vec<char> = sentence.chars().collect();
+        for &hmm in &[false, true] {
+            for token in jieba.tokenize(sentence, TokenizeMode::Search, hmm) {
+                let expected: String = chars[token.start..token.end].iter().collect();
+                assert_eq!(token.word, expected);
+            }
+        }
+    }
+
     #[test]
     fn test_tokenize() {
         let jieba = Jieba::new();
@@ -1394,6 +6119,87 @@ mod tests {
         assert_eq!(jieba.suggest_freq("中出"), 500)
     }
 
+    #[test]
+    fn test_empty_dict() {
+        let mut jieba = Jieba::empty();
+        assert_eq!(jieba.cut("网球拍卖会", false), vec!["网", "球", "拍", "卖", "会"]);
+        assert_eq!(jieba.cut("", true), Vec::<&str>::new());
+        assert!(jieba.suggest_freq("网球") > 0);
+
+        jieba.add_word("网球", Some(10), None);
+        jieba.add_word("拍卖会", Some(10), None);
+        assert_eq!(jieba.cut("网球拍卖会", false), vec!["网球", "拍卖会"]);
+        assert_eq!(jieba.cut("网球拍卖会", true), vec!["网球", "拍卖会"]);
+    }
+
+    #[test]
+    fn test_single_word_fast_path() {
+        let mut jieba = Jieba::new();
+        for word in &["北京大学", "我们", "中华人民共和国", "的", "c#"] {
+            assert!(jieba.is_single_word(word));
+            assert_eq!(jieba.cut(word, false), vec![*word]);
+            assert_eq!(jieba.cut(word, true), vec![*word]);
+        }
+        assert!(!jieba.is_single_word("北京 "));
+        assert!(!jieba.is_single_word("北京大"));
+        for sentence in &[
+            "研究生命",
+            "中华",
+            "乒乓球拍",
+            "结合成分子",
+            "一个叛徒",
+            "大学",
+            "拖拉机",
+        ] {
+            if jieba.is_single_word(sentence) {
+                assert_eq!(jieba.cut_nbest(sentence, 1)[0].0, vec![*sentence]);
+            }
+        }
+
+        // A rare word loses against its chars, routing has to decide
+        jieba.add_word("中出", Some(1), None);
+        assert!(!jieba.is_single_word("中出"));
+        assert_eq!(jieba.cut("中出", false), vec!["中", "出"]);
+
+        jieba.add_word("中出", Some(0), None);
+        assert!(!jieba.is_single_word("中出"));
+
+        // "丙" is skipped by routing and given the fallback frequency instead
+        let mut dict = "甲乙丙 10\n甲乙 100\n丙 0\n".as_bytes();
+        let jieba = Jieba::builder().fallback_freq(50).build_with_dict(&mut dict).unwrap();
+        assert!(!jieba.is_single_word("甲乙丙"));
+        assert_eq!(jieba.cut("甲乙丙", false), vec!["甲乙", "丙"]);
+    }
+
+    #[test]
+    fn test_calc_tie_break() {
+        // "甲乙" + "丙" and "甲" + "乙丙" are exactly as likely, the longer first word wins
+        let dict = "甲乙 10\n乙丙 10\n甲 10\n乙 10\n丙 10";
+        let jieba = Jieba::with_dict(&mut BufReader::new(dict.as_bytes())).unwrap();
+        assert_eq!(jieba.explain("甲乙丙", false)[0].1, jieba.explain("甲乙丙", false)[1].1);
+        for _ in 0..3 {
+            assert_eq!(jieba.cut("甲乙丙", false), vec!["甲乙", "丙"]);
+        }
+
+        let (a, b) = ((-1.0, 3), (-1.0, 6));
+        assert_eq!(super::cmp_route(&a, &b), std::cmp::Ordering::Less);
+        assert_eq!(super::cmp_route(&(f64::NAN, 6), &a), std::cmp::Ordering::Less);
+        assert_eq!(super::cmp_route(&a, &(f64::NAN, 1)), std::cmp::Ordering::Greater);
+    }
+
+    #[test]
+    fn test_zero_freq_dict() {
+        let dict = "网球 0\n球拍 0\n拍卖会 0\n网 0\n拍 0";
+        let jieba = Jieba::with_dict(&mut BufReader::new(dict.as_bytes())).unwrap();
+        assert_eq!(jieba.cut("网球拍卖会", false), vec!["网", "球", "拍", "卖", "会"]);
+        assert_eq!(jieba.cut("网球拍卖会", true).concat(), "网球拍卖会");
+
+        let mut jieba = Jieba::new();
+        assert_eq!(jieba.cut("网球拍卖会", false), vec!["网球", "拍卖会"]);
+        jieba.add_word("网球", Some(0), None);
+        assert_eq!(jieba.cut("网球拍卖会", false), vec!["网", "球", "拍卖会"]);
+    }
+
     #[test]
     fn test_cut_dag_no_hmm_against_string_with_sip() {
         let mut jieba = Jieba::empty();
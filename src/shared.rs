@@ -0,0 +1,130 @@
+use std::sync::Arc;
+
+use arc_swap::ArcSwap;
+
+use crate::Jieba;
+
+/// A [`Jieba`](struct.Jieba.html) shared between threads whose dict can be replaced while it's used
+///
+/// Cutting only loads the current instance, without taking any lock, so reads never wait for a
+/// dict update. An update builds a new instance and then swaps it in atomically, the cuts already
+/// running finish with the instance they started with.
+///
+/// ```rust
+/// use jieba_rs::{Jieba, SharedJieba};
+/// use std::sync::Arc;
+/// use std::thread;
+///
+/// let shared = Arc::new(SharedJieba::new(Jieba::new()));
+/// let reader = {
+///     let shared = Arc::clone(&shared);
+///     thread::spawn(move || shared.cut("我们中出了一个叛徒", false).len())
+/// };
+/// shared.update(|jieba| {
+///     jieba.add_word("叛徒", Some(1_000_000), None);
+/// });
+/// reader.join().unwrap();
+/// ```
+#[derive(Debug)]
+pub struct SharedJieba {
+    inner: ArcSwap<Jieba>,
+}
+
+impl SharedJieba {
+    /// Share `jieba`
+    pub fn new(jieba: Jieba) -> Self {
+        SharedJieba {
+            inner: ArcSwap::from_pointee(jieba),
+        }
+    }
+
+    /// Return the current instance, which isn't affected by the updates made after this call
+    ///
+    /// Useful to run several operations with the same dict.
+    pub fn load(&self) -> Arc<Jieba> {
+        self.inner.load_full()
+    }
+
+    /// Cut the input text with the current instance
+    ///
+    /// ## Params
+    ///
+    /// `sentence`: input text
+    ///
+    /// `hmm`: enable HMM or not
+    pub fn cut<'a>(&self, sentence: &'a str, hmm: bool) -> Vec<&'a str> {
+        self.inner.load().cut(sentence, hmm)
+    }
+
+    /// Replace the current instance with `jieba`, return the replaced instance
+    pub fn swap_dict(&self, jieba: Jieba) -> Arc<Jieba> {
+        self.inner.swap(Arc::new(jieba))
+    }
+
+    /// Update a copy of the current instance with `f` and swap it in
+    ///
+    /// `f` may be called more than once when other threads update the instance at the same time,
+    /// every call getting a fresh copy, so no update is lost.
+    pub fn update<F: Fn(&mut Jieba)>(&self, f: F) {
+        self.inner.rcu(|current| {
+            let mut jieba = Jieba::clone(current);
+            f(&mut jieba);
+            jieba
+        });
+    }
+}
+
+impl From<Jieba> for SharedJieba {
+    fn from(jieba: Jieba) -> Self {
+        SharedJieba::new(jieba)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::SharedJieba;
+    use crate::Jieba;
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::sync::Arc;
+    use std::thread;
+
+    #[test]
+    fn test_swap_dict_while_cutting() {
+        let first = Jieba::with_dict_str("南京 100\n市长 100\n").unwrap();
+        let second = Jieba::with_dict_str("南京市 100\n长江 100\n").unwrap();
+        let shared = Arc::new(SharedJieba::new(first.clone()));
+        let done = Arc::new(AtomicBool::new(false));
+
+        let readers: Vec<_> = (0..4)
+            .map(|_| {
+                let shared = Arc::clone(&shared);
+                let done = Arc::clone(&done);
+                thread::spawn(move || {
+                    let mut cuts = 0;
+                    while !done.load(Ordering::SeqCst) || cuts == 0 {
+                        let words = shared.cut("南京市长", false);
+                        assert!(words == vec!["南京", "市长"] || words == vec!["南京市", "长"]);
+                        cuts += 1;
+                    }
+                })
+            })
+            .collect();
+
+        for i in 0..100 {
+            let jieba = if i % 2 == 0 { second.clone() } else { first.clone() };
+            shared.swap_dict(jieba);
+        }
+        done.store(true, Ordering::SeqCst);
+        for reader in readers {
+            reader.join().unwrap();
+        }
+        assert_eq!(shared.cut("南京市长", false), vec!["南京", "市长"]);
+
+        let snapshot = shared.load();
+        shared.update(|jieba| {
+            jieba.add_word("南京市长", Some(1000), None);
+        });
+        assert_eq!(snapshot.cut("南京市长", false), vec!["南京", "市长"]);
+        assert_eq!(shared.cut("南京市长", false), vec!["南京市长"]);
+    }
+}
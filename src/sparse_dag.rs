@@ -1,5 +1,6 @@
 use hashbrown::HashMap;
 
+#[derive(Debug, Default)]
 pub(crate) struct StaticSparseDAG {
     array: Vec<usize>,
     start_pos: HashMap<usize, usize>,